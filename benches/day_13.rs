@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use adventofcode_2022::days::day_13::{compare_packets, PacketPiece};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+/// A tiny xorshift generator, so the packets are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, max: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % max
+    }
+}
+
+fn packet(rng: &mut Rng, depth: u32, out: &mut String) {
+    if depth > 0 && rng.next(3) == 0 {
+        out.push_str(&rng.next(11).to_string());
+        return;
+    }
+
+    out.push('[');
+    let len = if depth >= 4 { 0 } else { rng.next(5) };
+    for i in 0..len {
+        if i > 0 {
+            out.push(',');
+        }
+        packet(rng, depth + 1, out);
+    }
+    out.push(']');
+}
+
+fn packets(count: usize) -> Vec<String> {
+    let mut rng = Rng(0x2022_1213);
+
+    (0..count)
+        .map(|_| {
+            let mut s = String::new();
+            packet(&mut rng, 0, &mut s);
+            s
+        })
+        .collect()
+}
+
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day_13 sort");
+
+    for count in [1000, 10000] {
+        let packets = packets(count);
+        let trees: Vec<PacketPiece> = packets
+            .iter()
+            .map(|s| PacketPiece::from_str(s).unwrap())
+            .collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("parse and sort", count),
+            &packets,
+            |b, packets| {
+                b.iter(|| {
+                    let mut trees: Vec<PacketPiece> = packets
+                        .iter()
+                        .map(|s| PacketPiece::from_str(s).unwrap())
+                        .collect();
+                    trees.sort();
+                    trees
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("sort parsed", count),
+            &trees,
+            |b, trees| {
+                b.iter_batched(
+                    || trees.clone(),
+                    |mut trees| {
+                        trees.sort();
+                        trees
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("streaming", count),
+            &packets,
+            |b, packets| {
+                b.iter_batched(
+                    || packets.iter().map(String::as_str).collect::<Vec<&str>>(),
+                    |mut packets| {
+                        packets.sort_by(|a, b| compare_packets(a, b).unwrap());
+                        packets
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, sort);
+criterion_main!(benches);
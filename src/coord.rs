@@ -7,7 +7,7 @@ use std::{
 
 use crate::direction::Direction;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Coord<T>(pub T, pub T);
 
 impl Coord<i32> {
@@ -2,10 +2,56 @@
 
 use std::collections::{HashMap, HashSet};
 
-const TOTAL_DISK_SIZE: u32 = 70000000;
-const DISK_SPACE_NEEDED: u32 = 30000000;
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub total_disk_size: u32,
+    pub disk_space_needed: u32,
+    pub small_dir_limit: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            total_disk_size: 70000000,
+            disk_space_needed: 30000000,
+            small_dir_limit: 100000,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "total_disk_size" => self.total_disk_size = parse_param(key, value)?,
+            "disk_space_needed" => self.disk_space_needed = parse_param(key, value)?,
+            "small_dir_limit" => self.small_dir_limit = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.disk_space_needed > self.total_disk_size {
+            return Err(format!(
+                "disk_space_needed ({}) is more than total_disk_size ({})",
+                self.disk_space_needed, self.total_disk_size
+            ));
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
+    params.validate().unwrap_or_else(|e| panic!("{e}"));
+
     let mut current_path: Vec<&str> = vec!["/"];
     let mut filesystem = Filesystem {
         entries: HashMap::new(),
@@ -93,7 +139,8 @@ pub fn main(input: &str) -> (u32, u32) {
 
     let total_used = filesystem.get_recursive_size("/");
 
-    let need_to_delete = total_used - (TOTAL_DISK_SIZE - DISK_SPACE_NEEDED);
+    let need_to_delete =
+        total_used.saturating_sub(params.total_disk_size - params.disk_space_needed);
     let mut size_to_delete = u32::MAX;
 
     for (path, entry) in filesystem.entries.iter() {
@@ -104,7 +151,7 @@ pub fn main(input: &str) -> (u32, u32) {
                 size_to_delete = entry_size;
             }
 
-            if entry_size < 100000 {
+            if entry_size < params.small_dir_limit {
                 part1 += entry_size;
             }
         }
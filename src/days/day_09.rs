@@ -1,9 +1,18 @@
 use std::collections::HashSet;
+use std::fmt::Display;
 use std::str::FromStr;
 
-use crate::direction::Direction;
+use crate::{direction::Direction, visualize::Visualizer};
 
 pub fn main(input: &str) -> (u32, u32) {
+    simulate(input, |_| true)
+}
+
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    simulate(input, |frame| visualizer.frame(frame));
+}
+
+fn simulate(input: &str, mut on_step: impl FnMut(&RopeFrame) -> bool) -> (u32, u32) {
     let mut rope1 = Rope::new(2);
     let mut visited1: HashSet<Position> = HashSet::new();
     visited1.insert(Position(0, 0));
@@ -11,7 +20,7 @@ pub fn main(input: &str) -> (u32, u32) {
     let mut visited2: HashSet<Position> = HashSet::new();
     visited2.insert(Position(0, 0));
 
-    for line in input.lines() {
+    'motions: for line in input.lines() {
         let mut pieces = line.split_whitespace();
         let direction = Direction::from_str(pieces.next().unwrap()).unwrap();
         let num: usize = pieces.next().unwrap().parse().unwrap();
@@ -23,34 +32,17 @@ pub fn main(input: &str) -> (u32, u32) {
             rope2.move_dir(direction);
             visited2.insert(*rope2.tail().unwrap());
 
+            let frame = RopeFrame {
+                rope: &rope2,
+                visited: &visited2,
+            };
+
             if cfg!(feature = "debug") {
-                for y in (0..50).rev() {
-                    for x in 0..50 {
-                        if rope2.head().unwrap() == &Position(x, y) {
-                            print!("H");
-                        } else if rope2.tail().unwrap() == &Position(x, y) {
-                            print!("T");
-                        } else {
-                            print!(".");
-                        }
-                    }
-                    println!();
-                }
-                print!("\t");
-
-                for y in (0..50).rev() {
-                    for x in 0..50 {
-                        if rope2.head().unwrap() == &Position(x, y) {
-                            print!("H");
-                        } else if rope2.tail().unwrap() == &Position(x, y) {
-                            print!("T");
-                        } else {
-                            print!(".");
-                        }
-                    }
-                    println!();
-                }
-                println!();
+                println!("{frame}");
+            }
+
+            if !on_step(&frame) {
+                break 'motions;
             }
         }
     }
@@ -58,6 +50,40 @@ pub fn main(input: &str) -> (u32, u32) {
     (visited1.len() as _, visited2.len() as _)
 }
 
+/// A rope along with the positions its tail has visited, drawn like the
+/// puzzle's diagrams with `s` marking the starting position.
+struct RopeFrame<'a> {
+    rope: &'a Rope,
+    visited: &'a HashSet<Position>,
+}
+
+impl Display for RopeFrame<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let positions = || self.visited.iter().chain(self.rope.knots.iter());
+        let min_x = positions().map(|p| p.0).min().unwrap_or(0);
+        let max_x = positions().map(|p| p.0).max().unwrap_or(0);
+        let min_y = positions().map(|p| p.1).min().unwrap_or(0);
+        let max_y = positions().map(|p| p.1).max().unwrap_or(0);
+
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                let pos = Position(x, y);
+                match self.rope.knots.iter().position(|knot| *knot == pos) {
+                    Some(0) => write!(f, "H")?,
+                    Some(i) if i == self.rope.knots.len() - 1 => write!(f, "T")?,
+                    Some(i) => write!(f, "{i}")?,
+                    None if pos == Position(0, 0) => write!(f, "s")?,
+                    None if self.visited.contains(&pos) => write!(f, "#")?,
+                    None => write!(f, ".")?,
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
 struct Rope {
     knots: Vec<Position>,
 }
@@ -69,10 +95,6 @@ impl Rope {
         }
     }
 
-    fn head(&self) -> Option<&Position> {
-        self.knots.first()
-    }
-
     fn tail(&self) -> Option<&Position> {
         self.knots.last()
     }
@@ -1,45 +1,274 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub width: u32,
+    pub height: u32,
+    pub sprite_width: u32,
+    /// The first cycle whose signal strength is sampled.
+    pub sample_start: u32,
+    /// Cycles between samples after the first, or 0 to only sample once.
+    pub sample_interval: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+            sample_start: 20,
+            sample_interval: 40,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "width" => self.width = parse_param(key, value)?,
+            "height" => self.height = parse_param(key, value)?,
+            "sprite_width" => self.sprite_width = parse_param(key, value)?,
+            "sample_start" => self.sample_start = parse_param(key, value)?,
+            "sample_interval" => self.sample_interval = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.width == 0 {
+            return Err("width must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl Params {
+    pub fn screen(&self) -> Screen {
+        Screen::new(self.width, self.height, self.sprite_width)
+    }
+
+    fn is_sampled(&self, cycle: u32) -> bool {
+        cycle >= self.sample_start
+            && (cycle - self.sample_start).is_multiple_of(self.sample_interval)
+    }
+}
 
 pub fn main(input: &str) -> (String, String) {
-    let mut cpu = CPU::new(input);
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (String, String) {
+    let (sig_strength, screen) = run(input, params);
+
+    (
+        sig_strength.to_string(),
+        screen.read().unwrap_or_else(|_| screen.to_string()),
+    )
+}
+
+/// Runs the program, returning the signal strength along with the raw screen.
+pub fn run(input: &str, params: &Params) -> (i32, Screen) {
+    let mut screen = params.screen();
+
+    let sig_strength = CPU::new(assemble(input).unwrap())
+        .inspect(|&(cycle, x)| screen.draw(cycle, x))
+        .filter(|&(cycle, _)| params.is_sampled(cycle))
+        .map(|(cycle, x)| cycle as i32 * x)
+        .sum();
+
+    (sig_strength, screen)
+}
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_SPACING: usize = 1;
 
-    let mut sig_strength = 0;
-    let mut screen = String::new();
+/// The 4x6 font the puzzles draw their letters with.
+const GLYPHS: &[(char, &str)] = &[
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    width: u32,
+    height: u32,
+    sprite_width: u32,
+    rows: Vec<Vec<bool>>,
+}
 
-    while cpu.pc < cpu.program.len() || cpu.pending_instruction.is_some() {
-        if cpu.pc < cpu.program.len() && cpu.pending_instruction.is_none() {
-            cpu.load_instruction();
+impl Default for Screen {
+    fn default() -> Self {
+        Params::default().screen()
+    }
+}
+
+impl Screen {
+    pub fn new(width: u32, height: u32, sprite_width: u32) -> Self {
+        Screen {
+            width,
+            height,
+            sprite_width,
+            rows: Vec::new(),
         }
+    }
 
-        let x_pos = cpu.cycle % 40;
+    /// Whether a sprite positioned at `x` covers the pixel at `x_pos`.
+    fn sprite_covers(&self, x: i32, x_pos: i32) -> bool {
+        let left = x - (self.sprite_width / 2) as i32;
+        (left..left + self.sprite_width as i32).contains(&x_pos)
+    }
 
-        if x_pos == 0 {
-            screen.push_str("\n");
+    /// Lights the pixel under the beam during (1-based) `cycle` if the sprite
+    /// covers it. Cycles past the bottom of the screen draw nothing.
+    fn draw(&mut self, cycle: u32, x: i32) {
+        let pixel = cycle - 1;
+        if pixel / self.width >= self.height {
+            return;
         }
 
-        if (x_pos as i32).abs_diff(cpu.x) <= 1 {
-            screen.push_str("#");
-        } else {
-            screen.push_str(".");
+        let x_pos = pixel % self.width;
+        if x_pos == 0 {
+            self.rows.push(Vec::new());
         }
 
-        if cpu.cycle == 20 || (cpu.cycle > 20 && (cpu.cycle - 20) % 40 == 0) {
-            sig_strength += cpu.cycle as i32 * cpu.x;
+        let lit = self.sprite_covers(x, x_pos as i32);
+        self.rows.last_mut().unwrap().push(lit);
+    }
+
+    /// Recognizes the letters drawn on the screen.
+    pub fn read(&self) -> Result<String, String> {
+        let width = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        (0..width)
+            .step_by(GLYPH_WIDTH + GLYPH_SPACING)
+            .map(|x| {
+                let glyph: String = self
+                    .rows
+                    .iter()
+                    .flat_map(|row| (x..(x + GLYPH_WIDTH)).map(|x| row.get(x).copied()))
+                    .map(|lit| if lit == Some(true) { '#' } else { '.' })
+                    .collect();
+
+                GLYPHS
+                    .iter()
+                    .find_map(|(c, pixels)| if *pixels == glyph { Some(*c) } else { None })
+                    .ok_or(format!("unrecognized glyph at x={x}: {glyph}"))
+            })
+            .collect()
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in &self.rows {
+            writeln!(f)?;
+            for lit in row {
+                write!(f, "{}", if *lit { '#' } else { '.' })?;
+            }
         }
 
-        cpu.complete_cycle();
+        Ok(())
+    }
+}
+
+impl FromStr for Screen {
+    type Err = String;
 
-        cpu.cycle += 1;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<Vec<bool>> = s
+            .trim()
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .chars()
+                    .map(|c| match c {
+                        '#' => Ok(true),
+                        '.' => Ok(false),
+                        c => Err(format!("invalid pixel {c}")),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Screen {
+            width: rows.iter().map(Vec::len).max().unwrap_or(0) as u32,
+            height: rows.len() as u32,
+            sprite_width: Params::default().sprite_width,
+            rows,
+        })
     }
+}
+
+#[test]
+fn screen_read_works() {
+    let screen = Screen::from_str(
+        "
+#..#.####.#....#.....##..
+#..#.#....#....#....#..#.
+####.###..#....#....#..#.
+#..#.#....#....#....#..#.
+#..#.#....#....#....#..#.
+#..#.####.####.####..##..",
+    )
+    .unwrap();
 
-    (sig_strength.to_string(), screen)
+    assert_eq!(screen.read(), Ok("HELLO".to_string()));
+    assert_eq!(Screen::from_str(&screen.to_string()), Ok(screen));
+
+    assert!(Screen::from_str("#.#.\n.#.#").unwrap().read().is_err());
+}
+
+#[test]
+fn states_work() {
+    let cpu = CPU::new(assemble("noop\naddx 3\naddx -5").unwrap());
+    assert_eq!(
+        cpu.collect::<Vec<_>>(),
+        vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)]
+    );
+
+    let params = Params {
+        width: 3,
+        height: 1,
+        sprite_width: 1,
+        sample_start: 2,
+        sample_interval: 2,
+    };
+    let (sig_strength, screen) = run("noop\naddx 3\naddx -5", &params);
+    assert_eq!(sig_strength, 2 + 4 * 4);
+    assert_eq!(screen.to_string(), "\n.#.");
 }
 
-#[derive(Debug)]
-struct CPU {
+#[derive(Debug, Clone)]
+pub struct CPU {
     cycle: u32,
 
-    x: i32,
+    registers: [i32; Register::ALL.len()],
 
     pc: usize,
     program: Vec<Instruction>,
@@ -47,19 +276,38 @@ struct CPU {
 }
 
 impl CPU {
-    fn new(program: &str) -> Self {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        let mut registers = [0; Register::ALL.len()];
+        registers[Register::X as usize] = 1;
+
         CPU {
             cycle: 0,
-            x: 1,
+            registers,
             pc: 0,
-            program: program
-                .lines()
-                .map(|s| Instruction::from_str(s).unwrap())
-                .collect(),
+            program,
             pending_instruction: None,
         }
     }
 
+    pub fn register(&self, register: Register) -> i32 {
+        self.registers[register as usize]
+    }
+
+    pub fn x(&self) -> i32 {
+        self.register(Register::X)
+    }
+
+    fn operand(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Register(r) => self.register(r),
+            Operand::Literal(val) => val,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len() && self.pending_instruction.is_none()
+    }
+
     fn complete_cycle(&mut self) {
         if let Some(mut inst) = self.pending_instruction {
             inst.1 -= 1;
@@ -82,41 +330,780 @@ impl CPU {
         match instruction {
             Instruction::Noop => {}
             Instruction::Addx(op) => {
-                self.x += op as i32;
+                self.registers[Register::X as usize] += op;
+            }
+            Instruction::Add(r, op) => {
+                self.registers[r as usize] += self.operand(op);
+            }
+            Instruction::Mov(r, op) => {
+                self.registers[r as usize] = self.operand(op);
+            }
+            Instruction::Jmp(target) => {
+                self.pc = target;
+            }
+            Instruction::Jz(r, target) => {
+                if self.register(r) == 0 {
+                    self.pc = target;
+                }
+            }
+            Instruction::Jnz(r, target) => {
+                if self.register(r) != 0 {
+                    self.pc = target;
+                }
+            }
+            Instruction::Jlt(r, op, target) => {
+                if self.register(r) < self.operand(op) {
+                    self.pc = target;
+                }
+            }
+        }
+    }
+}
+
+/// Runs the CPU a cycle at a time, yielding each (1-based) cycle along with
+/// the value of `x` during it until the program halts.
+impl Iterator for CPU {
+    type Item = (u32, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_halted() {
+            return None;
+        }
+
+        if self.pending_instruction.is_none() {
+            self.load_instruction();
+        }
+
+        let x = self.x();
+        self.complete_cycle();
+        self.cycle += 1;
+
+        Some((self.cycle, x))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    X,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Register {
+    pub const ALL: [Register; 5] = [
+        Register::X,
+        Register::A,
+        Register::B,
+        Register::C,
+        Register::D,
+    ];
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Register::X => "x",
+                Register::A => "a",
+                Register::B => "b",
+                Register::C => "c",
+                Register::D => "d",
             }
+        )
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Register::ALL
+            .into_iter()
+            .find(|r| r.to_string() == s)
+            .ok_or(format!("invalid register {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Literal(i32),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{r}"),
+            Operand::Literal(val) => write!(f, "{val}"),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-enum Instruction {
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i32>() {
+            Ok(val) => Ok(Operand::Literal(val)),
+            Err(_) => Register::from_str(s)
+                .map(Operand::Register)
+                .map_err(|_| format!("invalid operand {s}")),
+        }
+    }
+}
+
+/// Jump targets are resolved to program addresses when assembled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
     Noop,
     Addx(i32),
+    Add(Register, Operand),
+    Mov(Register, Operand),
+    Jmp(usize),
+    Jz(Register, usize),
+    Jnz(Register, usize),
+    Jlt(Register, Operand, usize),
 }
 
 impl Instruction {
     fn cycles(&self) -> u32 {
         match self {
-            Instruction::Noop => 1,
-            Instruction::Addx(_) => 2,
+            Instruction::Addx(_) | Instruction::Add(_, _) => 2,
+            _ => 1,
+        }
+    }
+
+    fn target(&self) -> Option<usize> {
+        match self {
+            Instruction::Jmp(target)
+            | Instruction::Jz(_, target)
+            | Instruction::Jnz(_, target)
+            | Instruction::Jlt(_, _, target) => Some(*target),
+            _ => None,
+        }
+    }
+
+    /// Parses a single instruction, looking jump targets up in `labels` if
+    /// they aren't plain addresses.
+    fn parse(s: &str, labels: &HashMap<&str, usize>) -> Result<Self, String> {
+        let mut pieces = s.split_whitespace();
+        let mut next = |name: &str| pieces.next().ok_or(format!("missing {name}"));
+        let target = |label: &str| {
+            label
+                .parse()
+                .ok()
+                .or_else(|| labels.get(label).copied())
+                .ok_or(format!("unknown label {label}"))
+        };
+
+        let instruction = match next("operation")? {
+            "noop" => Instruction::Noop,
+            "addx" => {
+                let operand = next("operand")?;
+                let operand: i32 = operand
+                    .parse()
+                    .map_err(|_| format!("invalid operand {operand}"))?;
+                Instruction::Addx(operand)
+            }
+            "add" => Instruction::Add(next("register")?.parse()?, next("operand")?.parse()?),
+            "mov" => Instruction::Mov(next("register")?.parse()?, next("operand")?.parse()?),
+            "jmp" => Instruction::Jmp(target(next("target")?)?),
+            "jz" => Instruction::Jz(next("register")?.parse()?, target(next("target")?)?),
+            "jnz" => Instruction::Jnz(next("register")?.parse()?, target(next("target")?)?),
+            "jlt" => Instruction::Jlt(
+                next("register")?.parse()?,
+                next("operand")?.parse()?,
+                target(next("target")?)?,
+            ),
+            x => return Err(format!("invalid operation {x}")),
+        };
+
+        match pieces.next() {
+            Some(extra) => Err(format!("unexpected {extra}")),
+            None => Ok(instruction),
+        }
+    }
+
+    fn fmt_with_target(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        target: impl Display,
+    ) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(op) => write!(f, "addx {op}"),
+            Instruction::Add(r, op) => write!(f, "add {r} {op}"),
+            Instruction::Mov(r, op) => write!(f, "mov {r} {op}"),
+            Instruction::Jmp(_) => write!(f, "jmp {target}"),
+            Instruction::Jz(r, _) => write!(f, "jz {r} {target}"),
+            Instruction::Jnz(r, _) => write!(f, "jnz {r} {target}"),
+            Instruction::Jlt(r, op, _) => write!(f, "jlt {r} {op} {target}"),
         }
     }
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_with_target(f, self.target().unwrap_or_default())
+    }
+}
+
 impl FromStr for Instruction {
     type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Instruction::parse(s, &HashMap::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Strips comments and splits off a leading `label:`, if any.
+fn split_line(line: &str) -> (Option<&str>, &str) {
+    let line = line.split(';').next().unwrap_or_default().trim();
+
+    match line.split_once(':') {
+        Some((label, rest)) => (Some(label.trim()), rest.trim()),
+        None => (None, line),
+    }
+}
+
+/// Assembles a program, one instruction per line. Lines may start with a
+/// `label:` to jump to, and anything after a `;` is a comment.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AssemblyError> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut address = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let (label, rest) = split_line(line);
+        if let Some(label) = label {
+            if label.is_empty() || label.contains(char::is_whitespace) {
+                return Err(AssemblyError {
+                    line: i + 1,
+                    message: format!("invalid label {label}"),
+                });
+            }
+
+            if labels.insert(label, address).is_some() {
+                return Err(AssemblyError {
+                    line: i + 1,
+                    message: format!("duplicate label {label}"),
+                });
+            }
+        }
+
+        if !rest.is_empty() {
+            address += 1;
+        }
+    }
+
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, split_line(line).1))
+        .filter(|(_, rest)| !rest.is_empty())
+        .map(|(i, rest)| {
+            let instruction =
+                Instruction::parse(rest, &labels).map_err(|message| AssemblyError {
+                    line: i + 1,
+                    message,
+                })?;
+
+            // Jumping just past the end halts the program, anywhere further
+            // is a mistake.
+            match instruction.target() {
+                Some(target) if target > address => Err(AssemblyError {
+                    line: i + 1,
+                    message: format!("jump target {target} is past the end of the program"),
+                }),
+                _ => Ok(instruction),
+            }
+        })
+        .collect()
+}
+
+/// Turns a program back into source that assembles to the same program, with
+/// a label for each jump target.
+pub fn disassemble(program: &[Instruction]) -> String {
+    let mut targets: Vec<usize> = program.iter().filter_map(Instruction::target).collect();
+    targets.sort();
+    targets.dedup();
+
+    let label = |address: usize| format!("L{}", targets.binary_search(&address).unwrap());
+
+    struct Line<'a, F: Fn(usize) -> String>(&'a Instruction, &'a F);
+
+    impl<F: Fn(usize) -> String> Display for Line<'_, F> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.0
+                .fmt_with_target(f, self.0.target().map(self.1).unwrap_or_default())
+        }
+    }
+
+    let mut source = String::new();
+    for (address, instruction) in program.iter().enumerate() {
+        if targets.binary_search(&address).is_ok() {
+            source.push_str(&format!("{}:\n", label(address)));
+        }
+        source.push_str(&format!("    {}\n", Line(instruction, &label)));
+    }
+
+    // Jumping just past the end of the program halts it.
+    if targets.binary_search(&program.len()).is_ok() {
+        source.push_str(&format!("{}:\n", label(program.len())));
+    }
+
+    source
+}
+
+#[test]
+fn assembler_works() {
+    let source = "
+    mov a 5         ; count down from 5
+loop:
+    addx 2
+    add a -1
+    jnz a loop
+grow:
+    addx 3
+    jlt x 20 grow
+end: noop
+";
+    let program = assemble(source).unwrap();
+    assert_eq!(
+        program,
+        vec![
+            Instruction::Mov(Register::A, Operand::Literal(5)),
+            Instruction::Addx(2),
+            Instruction::Add(Register::A, Operand::Literal(-1)),
+            Instruction::Jnz(Register::A, 1),
+            Instruction::Addx(3),
+            Instruction::Jlt(Register::X, Operand::Literal(20), 4),
+            Instruction::Noop,
+        ]
+    );
+    assert_eq!(assemble(&disassemble(&program)), Ok(program.clone()));
+
+    let mut cpu = CPU::new(program);
+    cpu.by_ref().for_each(drop);
+    assert_eq!(cpu.x(), 20);
+    assert_eq!(cpu.register(Register::A), 0);
+
+    assert_eq!(
+        assemble("noop\naddx\n"),
+        Err(AssemblyError {
+            line: 2,
+            message: "missing operand".to_string()
+        })
+    );
+    assert_eq!(
+        assemble("jmp nowhere").unwrap_err().to_string(),
+        "line 1: unknown label nowhere"
+    );
+    assert!(assemble("a: noop\na: noop").is_err());
+    assert_eq!(
+        assemble("noop\njmp 99").unwrap_err().to_string(),
+        "line 2: jump target 99 is past the end of the program"
+    );
+    assert_eq!(
+        assemble("noop\njmp 2"),
+        Ok(vec![Instruction::Noop, Instruction::Jmp(2)])
+    );
+}
+
+/// Steps through a program on the command line, reading a command per line
+/// from `commands` until the program halts or `quit` is entered.
+pub fn debug(
+    input: &str,
+    params: &Params,
+    commands: impl BufRead,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let program =
+        assemble(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let mut debugger = Debugger::new(CPU::new(program), params.screen());
+
+    writeln!(out, "{}", Debugger::HELP)?;
+    write!(out, "{}\n(cpu) ", debugger.status())?;
+    out.flush()?;
+
+    for line in commands.lines() {
+        let line = line?;
+        if matches!(line.trim(), "q" | "quit") {
+            break;
+        }
+
+        match debugger.command(&line) {
+            Ok(output) => write!(out, "{output}")?,
+            Err(e) => writeln!(out, "error: {e}")?,
+        }
+        write!(out, "(cpu) ")?;
+        out.flush()?;
+    }
+
+    writeln!(out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops before the given (1-based) cycle starts.
+    Cycle(u32),
+    /// Stops before the instruction at the given address is loaded.
+    Pc(usize),
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Breakpoint::Pc(pc) => write!(f, "pc {pc}"),
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut pieces = s.split_whitespace();
-        match pieces.next().ok_or("missing operation".to_string())? {
-            "noop" => Ok(Instruction::Noop),
-            "addx" => {
-                let operand = pieces.next().ok_or("missing operand".to_string())?;
-                let operand: i32 = operand
+        let kind = pieces.next().ok_or("missing breakpoint kind".to_string())?;
+        let value = pieces
+            .next()
+            .ok_or("missing breakpoint value".to_string())?;
+
+        match kind {
+            "cycle" => Ok(Breakpoint::Cycle(
+                value
                     .parse()
-                    .map_err(|_| format!("invalid operand {operand}"))?;
-                Ok(Instruction::Addx(operand))
+                    .map_err(|_| format!("invalid cycle {value}"))?,
+            )),
+            "pc" => Ok(Breakpoint::Pc(
+                value.parse().map_err(|_| format!("invalid pc {value}"))?,
+            )),
+            kind => Err(format!("invalid breakpoint kind {kind}")),
+        }
+    }
+}
+
+pub struct Debugger {
+    cpu: CPU,
+    screen: Screen,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Register>,
+}
+
+impl Debugger {
+    const HELP: &'static str = "\
+commands:
+  s, step [n]             run n cycles (default 1)
+  c, continue [n]         run until a breakpoint, watchpoint or halt, or
+                          for at most n cycles (default 1000000)
+  b, break cycle|pc <n>   stop before cycle n starts or address n is loaded
+  w, watch <register>     stop whenever the register changes
+  d, delete <n>           delete breakpoint n
+  i, info                 list breakpoints and watchpoints
+  r, regs                 dump registers
+  l, list                 disassemble around the program counter
+  crt                     show the CRT row being drawn
+  q, quit                 exit";
+
+    /// How many cycles `continue` runs for at most, so that a program that
+    /// never halts doesn't hang the debugger.
+    const CONTINUE_LIMIT: u32 = 1_000_000;
+
+    pub fn new(cpu: CPU, screen: Screen) -> Self {
+        Debugger {
+            cpu,
+            screen,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+        }
+    }
+
+    /// Runs a single debugger command, returning what it printed.
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let mut pieces = line.split_whitespace();
+        let rest = || {
+            line.trim()
+                .split_once(' ')
+                .map(|(_, rest)| rest)
+                .unwrap_or_default()
+        };
+
+        match pieces.next().unwrap_or("s") {
+            "s" | "step" => {
+                let n: u32 = match pieces.next() {
+                    Some(n) => n.parse().map_err(|_| format!("invalid count {n}"))?,
+                    None => 1,
+                };
+                let mut output = String::new();
+                for _ in 0..n {
+                    if let Some(reason) = self.step() {
+                        output.push_str(&reason);
+                        break;
+                    }
+                }
+                Ok(output + &self.status())
             }
-            x => Err(format!("invalid operation {x}")),
+            "c" | "continue" => {
+                let limit: u32 = match pieces.next() {
+                    Some(n) => n.parse().map_err(|_| format!("invalid count {n}"))?,
+                    None => Debugger::CONTINUE_LIMIT,
+                };
+                let mut reason = self.step();
+                let mut cycles = 1;
+                while reason.is_none() && !self.cpu.is_halted() && !self.at_breakpoint() {
+                    if cycles >= limit {
+                        reason = Some(format!(
+                            "stopped after {cycles} cycles at {}\n",
+                            self.location()
+                        ));
+                        break;
+                    }
+                    reason = self.step();
+                    cycles += 1;
+                }
+                let reason = reason.unwrap_or_else(|| {
+                    if self.cpu.is_halted() {
+                        "program halted\n".to_string()
+                    } else {
+                        format!("breakpoint at {}\n", self.location())
+                    }
+                });
+                Ok(reason + &self.status())
+            }
+            "b" | "break" => {
+                let breakpoint = Breakpoint::from_str(rest())?;
+                self.breakpoints.push(breakpoint);
+                Ok(format!(
+                    "breakpoint {}: {breakpoint}\n",
+                    self.breakpoints.len() - 1
+                ))
+            }
+            "w" | "watch" => {
+                let register = Register::from_str(rest())?;
+                self.watchpoints.push(register);
+                Ok(format!("watching {register}\n"))
+            }
+            "d" | "delete" => {
+                let i: usize = rest()
+                    .parse()
+                    .map_err(|_| format!("invalid breakpoint {}", rest()))?;
+                if i >= self.breakpoints.len() {
+                    return Err(format!("no breakpoint {i}"));
+                }
+                Ok(format!(
+                    "deleted breakpoint {}\n",
+                    self.breakpoints.remove(i)
+                ))
+            }
+            "i" | "info" => {
+                let mut output = String::new();
+                for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                    output.push_str(&format!("breakpoint {i}: {breakpoint}\n"));
+                }
+                for register in &self.watchpoints {
+                    output.push_str(&format!("watching {register}\n"));
+                }
+                Ok(output)
+            }
+            "r" | "regs" => Ok(self.registers()),
+            "l" | "list" => Ok(self.listing()),
+            "crt" => Ok(self.crt()),
+            "h" | "help" => Ok(format!("{}\n", Debugger::HELP)),
+            command => Err(format!("unknown command {command}")),
+        }
+    }
+
+    /// Runs one cycle, returning why execution should stop if it should.
+    fn step(&mut self) -> Option<String> {
+        if self.cpu.is_halted() {
+            return Some("program halted\n".to_string());
+        }
+
+        let before: Vec<i32> = self
+            .watchpoints
+            .iter()
+            .map(|r| self.cpu.register(*r))
+            .collect();
+
+        if let Some((cycle, x)) = self.cpu.next() {
+            self.screen.draw(cycle, x);
+        }
+
+        self.watchpoints
+            .iter()
+            .zip(before)
+            .find(|(r, before)| self.cpu.register(**r) != *before)
+            .map(|(r, before)| {
+                format!(
+                    "watchpoint: {r} changed from {before} to {} at end of cycle {}\n",
+                    self.cpu.register(*r),
+                    self.cpu.cycle
+                )
+            })
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Cycle(cycle) => self.cpu.cycle + 1 == *cycle,
+            Breakpoint::Pc(pc) => self.cpu.pending_instruction.is_none() && self.cpu.pc == *pc,
+        })
+    }
+
+    fn location(&self) -> String {
+        format!("cycle {}, pc {}", self.cpu.cycle + 1, self.cpu.pc)
+    }
+
+    fn status(&self) -> String {
+        if self.cpu.is_halted() {
+            format!("halted after cycle {}\n", self.cpu.cycle)
+        } else {
+            let instruction = match self.cpu.pending_instruction {
+                Some((instruction, remaining)) => {
+                    format!("{instruction} ({remaining} cycles left)")
+                }
+                None => format!("{} (not loaded)", self.cpu.program[self.cpu.pc]),
+            };
+            format!(
+                "{}: x={} | {instruction}\n{}",
+                self.location(),
+                self.cpu.x(),
+                self.crt()
+            )
         }
     }
+
+    fn registers(&self) -> String {
+        let mut output = format!("{}\n", self.location());
+        for register in Register::ALL {
+            output.push_str(&format!("{register}  {}\n", self.cpu.register(register)));
+        }
+        output
+    }
+
+    fn listing(&self) -> String {
+        let current = self
+            .cpu
+            .pc
+            .saturating_sub(usize::from(self.cpu.pending_instruction.is_some()));
+        let start = current.saturating_sub(3);
+        let end = (current + 4).min(self.cpu.program.len());
+
+        (start..end)
+            .map(|address| {
+                format!(
+                    "{}{address:>4}  {}\n",
+                    if address == current { "=>" } else { "  " },
+                    self.cpu.program[address]
+                )
+            })
+            .collect()
+    }
+
+    /// The row of the screen being drawn, with the sprite and beam beneath it.
+    fn crt(&self) -> String {
+        let width = self.screen.width;
+        let beam = self.cpu.cycle % width;
+        let row: String = if beam == 0 {
+            String::new()
+        } else {
+            self.screen
+                .rows
+                .last()
+                .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect())
+                .unwrap_or_default()
+        };
+        let sprite: String = (0..width as i32)
+            .map(|x_pos| {
+                if self.screen.sprite_covers(self.cpu.x(), x_pos) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        format!(
+            "row {:>2}  {row}\nsprite  {sprite}\nbeam    {}^\n",
+            self.cpu.cycle / width,
+            " ".repeat(beam as usize)
+        )
+    }
+}
+
+#[test]
+fn debugger_works() {
+    let program = assemble("noop\naddx 3\naddx -5\nnoop").unwrap();
+    let mut debugger = Debugger::new(CPU::new(program), Screen::default());
+
+    debugger.command("break pc 2").unwrap();
+    let output = debugger.command("continue").unwrap();
+    assert!(
+        output.starts_with("breakpoint at cycle 4, pc 2"),
+        "{output}"
+    );
+    assert_eq!(debugger.cpu.x(), 4);
+
+    debugger.command("watch x").unwrap();
+    debugger.command("delete 0").unwrap();
+    let output = debugger.command("c").unwrap();
+    assert!(
+        output.starts_with("watchpoint: x changed from 4 to -1 at end of cycle 5"),
+        "{output}"
+    );
+    assert!(
+        debugger.crt().starts_with("row  0  #####\n"),
+        "{}",
+        debugger.crt()
+    );
+
+    debugger.command("b cycle 7").unwrap();
+    assert_eq!(
+        debugger.command("c").unwrap(),
+        "program halted\nhalted after cycle 6\n"
+    );
+    assert!(debugger.command("b nowhere 1").is_err());
+
+    let program = assemble("loop: addx 1\n    jmp loop").unwrap();
+    let mut debugger = Debugger::new(CPU::new(program), Screen::default());
+    let output = debugger.command("c 100").unwrap();
+    assert!(
+        output.starts_with("stopped after 100 cycles at cycle 101,"),
+        "{output}"
+    );
+    let output = debugger.command("c").unwrap();
+    assert!(
+        output.starts_with("stopped after 1000000 cycles"),
+        "{output}"
+    );
+
+    let mut out = Vec::new();
+    debug(
+        "addx 1\nnoop",
+        &Params::default(),
+        "s 2\nregs\nq\ns\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("cycle 3, pc 1: x=2 | noop (not loaded)"),
+        "{out}"
+    );
+    assert!(out.contains("cycle 3, pc 1\nx  2\na  0\n"), "{out}");
+    assert!(!out.contains("halted"), "{out}");
 }
@@ -1,64 +1,193 @@
-use std::str::FromStr;
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
-use crate::operation::Operation;
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+
+use crate::{
+    operation::Operation,
+    params::{parse_param, unknown_param, Parameters},
+};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub part1_rounds: usize,
+    pub part2_rounds: usize,
+    pub part1_relief: Relief,
+    pub part2_relief: Relief,
+    /// Tracks worry levels as arbitrary-precision integers, so they can grow
+    /// without any relief at all.
+    pub bigint: bool,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            part1_rounds: 20,
+            part2_rounds: 10000,
+            part1_relief: Relief::Expression(Expression::from_str("old / 3").unwrap()),
+            part2_relief: Relief::CommonMultiple,
+            bigint: false,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "part1_rounds" => self.part1_rounds = parse_param(key, value)?,
+            "part2_rounds" => self.part2_rounds = parse_param(key, value)?,
+            "part1_relief" => self.part1_relief = parse_param(key, value)?,
+            "part2_relief" => self.part2_relief = parse_param(key, value)?,
+            "bigint" if cfg!(feature = "bigint") => self.bigint = parse_param(key, value)?,
+            "bigint" => return Err("bigint requires the `bigint` feature".to_string()),
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (u64, u64) {
-    let monkeys: Vec<Monkey> = input
-        .split("\n\n")
-        .map(|s| Monkey::from_str(s).unwrap())
-        .collect();
+    main_with_params(input, &Params::default())
+}
 
-    let part1 = throw_around(monkeys, 20, false);
+pub fn main_with_params(input: &str, params: &Params) -> (u64, u64) {
+    #[cfg(feature = "bigint")]
+    if params.bigint {
+        return run::<BigUint>(input, params);
+    }
+
+    run::<u64>(input, params)
+}
 
-    let monkeys: Vec<Monkey> = input
+fn run<W: Worry>(input: &str, params: &Params) -> (u64, u64) {
+    let monkeys: Vec<Monkey<W>> = parse(input).unwrap();
+
+    let part1 = simulate(monkeys.clone(), params.part1_rounds, &params.part1_relief).unwrap();
+    let part2 = simulate(monkeys, params.part2_rounds, &params.part2_relief).unwrap();
+
+    (monkey_business(&part1), monkey_business(&part2))
+}
+
+/// Parses every monkey in the input, checking that they only throw to
+/// monkeys that exist.
+pub fn parse<W: Worry>(input: &str) -> Result<Vec<Monkey<W>>, String> {
+    let monkeys: Vec<Monkey<W>> = input
         .split("\n\n")
-        .map(|s| Monkey::from_str(s).unwrap())
-        .collect();
+        .map(Monkey::from_str)
+        .collect::<Result<_, _>>()?;
 
-    let part2 = throw_around(monkeys, 10000, true);
+    for (i, monkey) in monkeys.iter().enumerate() {
+        if let Some(target) = monkey.targets().find(|target| *target >= monkeys.len()) {
+            return Err(format!("monkey {i} throws to missing monkey {target}"));
+        }
+    }
 
-    (part1, part2)
+    Ok(monkeys)
 }
 
-fn throw_around(mut monkeys: Vec<Monkey>, rounds: usize, worried: bool) -> u64 {
-    let common_multiple = monkeys
-        .iter()
-        .map(|m| m.divisible_test)
-        .fold(1, |acc, x| acc * x);
+/// Runs `rounds` rounds of keep away, returning the monkeys as they are
+/// afterwards.
+///
+/// `relief` is applied to each item after it's inspected, unless the monkey
+/// inspecting it declares its own. Fails if relief needs the monkeys' common
+/// multiple and it doesn't fit in a `u64`.
+pub fn simulate<W: Worry>(
+    monkeys: Vec<Monkey<W>>,
+    rounds: usize,
+    relief: &Relief,
+) -> Result<Vec<Monkey<W>>, String> {
+    Ok(simulate_traced(monkeys, rounds, relief, TraceOptions::default())?.0)
+}
 
-    let mut trues: Vec<u64> = Vec::new();
-    let mut falses: Vec<u64> = Vec::new();
+/// Like [`simulate`], but also records whatever `options` asks for.
+pub fn simulate_traced<W: Worry>(
+    mut monkeys: Vec<Monkey<W>>,
+    rounds: usize,
+    relief: &Relief,
+    options: TraceOptions,
+) -> Result<(Vec<Monkey<W>>, Trace<W>), String> {
+    let uses_common_multiple = matches!(relief, Relief::CommonMultiple)
+        || monkeys
+            .iter()
+            .any(|m| matches!(m.relief, Some(Relief::CommonMultiple)));
+    let common_multiple = if uses_common_multiple {
+        common_multiple(&monkeys)?
+    } else {
+        1
+    };
+
+    let mut trace = Trace::default();
+
+    // Items are numbered in the order they start out in, and their numbers
+    // follow them around alongside their worry levels.
+    let mut item_ids: Vec<Vec<usize>> = Vec::new();
+    if options.paths {
+        let mut next_id = 0;
+        for monkey in &monkeys {
+            item_ids.push((next_id..next_id + monkey.items.len()).collect());
+            next_id += monkey.items.len();
+        }
+        trace.paths = vec![Vec::new(); next_id];
+    }
+
+    let mut throws: Vec<(usize, W)> = Vec::new();
     for round in 0..rounds {
+        let mut inspections = Vec::with_capacity(monkeys.len());
+
         for i in 0..monkeys.len() {
-            let true_monkey: usize;
-            let false_monkey: usize;
-            {
-                let monkey = &mut monkeys[i];
-                true_monkey = monkey.if_divisible_true;
-                false_monkey = monkey.if_divisible_false;
-                for mut item_worry_level in monkey.items.drain(..) {
-                    if worried {
-                        item_worry_level =
-                            monkey.operation.as_mut()(item_worry_level) % common_multiple;
-                    } else {
-                        item_worry_level = monkey.operation.as_mut()(item_worry_level) / 3;
-                    }
+            let monkey = &monkeys[i];
+            let relief = monkey.relief.as_ref().unwrap_or(relief);
 
-                    if item_worry_level % monkey.divisible_test == 0 {
-                        trues.push(item_worry_level);
-                    } else {
-                        falses.push(item_worry_level);
-                    }
+            for item_worry_level in &monkey.items {
+                let item_worry_level = monkey
+                    .operation
+                    .evaluate(item_worry_level)
+                    .and_then(|worry_level| relief.apply(worry_level, common_multiple))
+                    .ok_or_else(|| {
+                        format!(
+                            "monkey {i} can't inspect an item with worry level {item_worry_level:?}: \
+                             `{}` then `{relief}` goes out of range",
+                            monkey.operation
+                        )
+                    })?;
+
+                throws.push((monkey.target(&item_worry_level), item_worry_level));
+            }
 
-                    monkey.total_inspections += 1;
+            let monkey = &mut monkeys[i];
+            inspections.push(monkey.items.len());
+            monkey.total_inspections += monkey.items.len();
+            monkey.items.clear();
+
+            if options.paths {
+                for (id, (target, worry_level)) in
+                    std::mem::take(&mut item_ids[i]).into_iter().zip(&throws)
+                {
+                    trace.paths[id].push(Throw {
+                        round: round + 1,
+                        from: i,
+                        to: *target,
+                        worry_level: worry_level.clone(),
+                    });
+                    item_ids[*target].push(id);
                 }
             }
-            monkeys[true_monkey].items.append(&mut trues);
-            monkeys[false_monkey].items.append(&mut falses);
+
+            for (target, item_worry_level) in throws.drain(..) {
+                monkeys[target].items.push(item_worry_level);
+            }
+        }
+
+        if options.inspections {
+            trace.inspections.push(inspections);
         }
 
         if cfg!(feature = "debug") {
-            if worried {
+            if matches!(relief, Relief::CommonMultiple) {
                 if (round + 1) % 1000 == 0 || round == 0 || round + 1 == 20 {
                     eprintln!("== After round {} ==", round + 1);
                     for (i, monkey) in monkeys.iter().enumerate() {
@@ -78,136 +207,636 @@ fn throw_around(mut monkeys: Vec<Monkey>, rounds: usize, worried: bool) -> u64 {
         }
     }
 
+    Ok((monkeys, trace))
+}
+
+/// The least common multiple of every monkey's divisors. Keeping worry
+/// levels modulo it doesn't change where any item gets thrown.
+pub fn common_multiple<W: Worry>(monkeys: &[Monkey<W>]) -> Result<u64, String> {
+    monkeys
+        .iter()
+        .flat_map(|m| m.tests.iter().map(|test| test.divisor))
+        .try_fold(1u64, |multiple, divisor| {
+            multiple
+                .checked_mul(divisor / gcd(multiple, divisor))
+                .ok_or("the common multiple of the monkeys' divisors overflows a u64".to_string())
+        })
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// What [`simulate_traced`] should record. Both can get large over
+/// thousands of rounds, so they're opt-in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceOptions {
+    pub paths: bool,
+    pub inspections: bool,
+}
+
+/// A single item being thrown from one monkey to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throw<W = u64> {
+    /// The (1-based) round the throw happened in.
+    pub round: usize,
+    pub from: usize,
+    pub to: usize,
+    /// The item's worry level after inspection and relief.
+    pub worry_level: W,
+}
+
+#[derive(Debug, Clone)]
+pub struct Trace<W = u64> {
+    /// Every throw each item took, indexed by the order the items start out
+    /// in.
+    pub paths: Vec<Vec<Throw<W>>>,
+    /// How many items each monkey inspected, indexed by round then monkey.
+    pub inspections: Vec<Vec<usize>>,
+}
+
+impl<W> Default for Trace<W> {
+    fn default() -> Self {
+        Trace {
+            paths: Vec::new(),
+            inspections: Vec::new(),
+        }
+    }
+}
+
+impl<W> Trace<W> {
+    /// Draws a bar per monkey of how many items it inspected in `round`.
+    pub fn histogram(&self, round: usize) -> Option<String> {
+        let inspections = self.inspections.get(round.checked_sub(1)?)?;
+        let width = inspections.len().to_string().len();
+
+        Some(
+            inspections
+                .iter()
+                .enumerate()
+                .map(|(i, count)| format!("Monkey {i:>width$} | {} {count}\n", "#".repeat(*count)))
+                .collect(),
+        )
+    }
+}
+
+/// The product of the two highest inspection counts.
+pub fn monkey_business<W>(monkeys: &[Monkey<W>]) -> u64 {
     let mut inspections: Vec<usize> = monkeys.iter().map(|m| m.total_inspections).collect();
     inspections.sort();
 
-    (inspections.pop().unwrap() * inspections.pop().unwrap()) as u64
+    inspections.iter().rev().take(2).product::<usize>() as u64
+}
+
+/// A worry level, either a plain `u64` or, with the `bigint` feature, an
+/// arbitrary-precision integer.
+pub trait Worry: Clone + Debug {
+    fn from_u64(x: u64) -> Self;
+
+    /// Performs `operation`, or returns `None` if the result can't be
+    /// represented, or on division by zero.
+    fn perform(operation: Operation, lhs: Self, rhs: Self) -> Option<Self>;
+
+    fn is_divisible_by(&self, divisor: u64) -> bool;
+
+    /// Reduces the worry level modulo `modulus`.
+    fn reduce(self, modulus: u64) -> Self;
 }
 
-struct Monkey {
-    items: Vec<u64>,
-    total_inspections: usize,
-    operation: Box<dyn Fn(u64) -> u64>,
-    divisible_test: u64,
-    if_divisible_true: usize,
-    if_divisible_false: usize,
+impl Worry for u64 {
+    fn from_u64(x: u64) -> Self {
+        x
+    }
+
+    fn perform(operation: Operation, lhs: Self, rhs: Self) -> Option<Self> {
+        match operation {
+            Operation::Add => lhs.checked_add(rhs),
+            Operation::Subtract => lhs.checked_sub(rhs),
+            Operation::Multiply => lhs.checked_mul(rhs),
+            Operation::Divide => lhs.checked_div(rhs),
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        self.is_multiple_of(divisor)
+    }
+
+    fn reduce(self, modulus: u64) -> Self {
+        self % modulus
+    }
 }
 
-impl Default for Monkey {
-    fn default() -> Self {
-        Monkey {
-            items: Vec::new(),
-            total_inspections: 0,
-            operation: Box::new(|x| x),
-            divisible_test: 0,
-            if_divisible_true: 0,
-            if_divisible_false: 0,
+#[cfg(feature = "bigint")]
+impl Worry for BigUint {
+    fn from_u64(x: u64) -> Self {
+        BigUint::from(x)
+    }
+
+    fn perform(operation: Operation, lhs: Self, rhs: Self) -> Option<Self> {
+        match operation {
+            Operation::Subtract if lhs < rhs => None,
+            Operation::Divide if rhs == BigUint::ZERO => None,
+            operation => Some(operation.perform(lhs, rhs)),
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> bool {
+        self % divisor == BigUint::ZERO
+    }
+
+    fn reduce(self, modulus: u64) -> Self {
+        self % modulus
+    }
+}
+
+/// How worry levels are brought back down after each inspection.
+#[derive(Debug, Clone)]
+pub enum Relief {
+    /// Replaces the worry level with the result of an expression, like
+    /// `old / 3`.
+    Expression(Expression),
+    /// Keeps worry levels modulo the least common multiple of every
+    /// monkey's divisors, which doesn't change where any item gets thrown.
+    CommonMultiple,
+}
+
+impl Relief {
+    fn apply<W: Worry>(&self, worry_level: W, common_multiple: u64) -> Option<W> {
+        match self {
+            Relief::Expression(expression) => expression.evaluate(&worry_level),
+            Relief::CommonMultiple => Some(worry_level.reduce(common_multiple)),
+        }
+    }
+}
+
+impl FromStr for Relief {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "common multiple" => Ok(Relief::CommonMultiple),
+            s => Ok(Relief::Expression(Expression::from_str(s)?)),
         }
     }
 }
 
+impl Display for Relief {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Relief::Expression(expression) => write!(f, "{expression}"),
+            Relief::CommonMultiple => write!(f, "common multiple"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Value {
-    Variable(String),
+    Old,
     Literal(u64),
 }
 
+impl Value {
+    fn resolve<W: Worry>(&self, old: &W) -> W {
+        match self {
+            Value::Old => old.clone(),
+            Value::Literal(x) => W::from_u64(*x),
+        }
+    }
+}
+
 impl FromStr for Value {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.parse::<u64>() {
-            Ok(val) => Ok(Value::Literal(val)),
-            _ => Ok(Value::Variable(s.to_string())),
+        match s {
+            "old" => Ok(Value::Old),
+            s => s
+                .parse()
+                .map(Value::Literal)
+                .map_err(|_| format!("invalid value {s}")),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Old => write!(f, "old"),
+            Value::Literal(x) => write!(f, "{x}"),
         }
     }
 }
 
-impl FromStr for Monkey {
+/// An arithmetic expression over `old`, evaluated strictly left to right,
+/// like `new = old * 19 + 2`.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    first: Value,
+    rest: Vec<(Operation, Value)>,
+}
+
+impl Expression {
+    /// Evaluates the expression, or returns `None` if any step goes out of
+    /// range.
+    pub fn evaluate<W: Worry>(&self, old: &W) -> Option<W> {
+        self.rest
+            .iter()
+            .try_fold(self.first.resolve(old), |acc, (operation, value)| {
+                W::perform(*operation, acc, value.resolve(old))
+            })
+    }
+}
+
+impl FromStr for Expression {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut monkey = Monkey::default();
+        let s = s.trim();
+        let mut pieces = s.strip_prefix("new =").unwrap_or(s).split_whitespace();
+
+        let first = Value::from_str(pieces.next().ok_or("empty expression".to_string())?)?;
+        let mut rest = Vec::new();
+        while let Some(operation) = pieces.next() {
+            let operation = Operation::from_str(operation)?;
+            let value = Value::from_str(pieces.next().ok_or(format!("missing value after {s}"))?)?;
+            if matches!((operation, value), (Operation::Divide, Value::Literal(0))) {
+                return Err(format!("division by zero in {s}"));
+            }
+            rest.push((operation, value));
+        }
+
+        Ok(Expression { first, rest })
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (operation, value) in &self.rest {
+            let operation = match operation {
+                Operation::Add => '+',
+                Operation::Subtract => '-',
+                Operation::Multiply => '*',
+                Operation::Divide => '/',
+            };
+            write!(f, " {operation} {value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Throws an item to `target` if its worry level is divisible by `divisor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Test {
+    pub divisor: u64,
+    pub target: usize,
+}
+
+/// A monkey, parsed from its puzzle description.
+///
+/// On top of the puzzle's format, a monkey can list several `Test`s, each
+/// followed by its own `If true` target. They're checked in order, and the
+/// item goes to the `If false` target when none of them pass. A monkey can
+/// also set its own `Relief: <expression>` to use instead of the
+/// simulation's.
+#[derive(Debug, Clone)]
+pub struct Monkey<W = u64> {
+    pub items: Vec<W>,
+    pub total_inspections: usize,
+    operation: Expression,
+    tests: Vec<Test>,
+    if_all_false: usize,
+    relief: Option<Relief>,
+}
+
+impl<W: Worry> Monkey<W> {
+    /// The monkey an item with the given worry level is thrown to.
+    pub fn target(&self, worry_level: &W) -> usize {
+        self.tests
+            .iter()
+            .find(|test| worry_level.is_divisible_by(test.divisor))
+            .map_or(self.if_all_false, |test| test.target)
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.tests
+            .iter()
+            .map(|test| test.target)
+            .chain([self.if_all_false])
+    }
+}
+
+fn parse_last<T: FromStr>(chunk: Option<&str>, name: &str) -> Result<T, String> {
+    let value = chunk
+        .ok_or(format!("missing chunk for {name}"))?
+        .split_whitespace()
+        .last()
+        .ok_or(format!("{name} missing value"))?;
+
+    value
+        .parse()
+        .map_err(|_| format!("invalid {name} value {value}"))
+}
+
+impl<W: Worry> FromStr for Monkey<W> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = Vec::new();
+        let mut operation = None;
+        let mut tests: Vec<(u64, Option<usize>)> = Vec::new();
+        let mut if_all_false = None;
+        let mut relief = None;
 
         for line in s.lines() {
-            if line.starts_with("Monkey ") {
+            if line.starts_with("Monkey ") || line.trim().is_empty() {
                 continue;
             }
 
-            let mut chunks = line.trim().split(": ");
+            let mut chunks = line.trim().splitn(2, ':').map(str::trim);
             match chunks.next().unwrap() {
                 "Starting items" => {
-                    monkey.items = chunks
+                    items = chunks
                         .next()
-                        .ok_or("missing chunk for starting items".to_string())?
-                        .trim()
-                        .split(", ")
-                        .map(|s| s.parse().unwrap())
-                        .collect();
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| {
+                            s.parse()
+                                .map(W::from_u64)
+                                .map_err(|_| format!("invalid item {s}"))
+                        })
+                        .collect::<Result<_, _>>()?;
                 }
                 "Operation" => {
-                    let mut pieces = chunks
-                        .next()
-                        .ok_or("missing chunk for starting items".to_string())?
-                        .split_whitespace()
-                        .skip(2);
-
-                    let lhs =
-                        Value::from_str(pieces.next().ok_or("missing operaton lhs".to_string())?)?;
-                    let operation = Operation::from_str(
-                        pieces
+                    operation = Some(Expression::from_str(
+                        chunks
                             .next()
-                            .ok_or("missing operaton operation".to_string())?,
-                    )?;
-                    let rhs =
-                        Value::from_str(pieces.next().ok_or("missing operaton rhs".to_string())?)?;
-
-                    monkey.operation = Box::new(move |old| {
-                        operation.perform(
-                            match lhs {
-                                Value::Literal(x) => x,
-                                Value::Variable(_) => old,
-                            },
-                            match rhs {
-                                Value::Literal(x) => x,
-                                Value::Variable(_) => old,
-                            },
-                        )
-                    });
+                            .ok_or("missing chunk for operation".to_string())?,
+                    )?);
+                }
+                "Relief" => {
+                    relief = Some(Relief::from_str(
+                        chunks
+                            .next()
+                            .ok_or("missing chunk for relief".to_string())?,
+                    )?);
                 }
                 "Test" => {
-                    monkey.divisible_test = chunks
-                        .next()
-                        .ok_or("missing chunk for test".to_string())?
-                        .split_whitespace()
-                        .last()
-                        .ok_or("test missing value".to_string())?
-                        .parse()
-                        .map_err(|e| format!("invalid test value {:?}", e))?;
+                    let divisor = parse_last(chunks.next(), "test")?;
+                    if divisor == 0 {
+                        return Err("test can't be divisible by 0".to_string());
+                    }
+                    tests.push((divisor, None));
                 }
                 "If true" => {
-                    monkey.if_divisible_true = chunks
-                        .next()
-                        .ok_or("missing chunk for true".to_string())?
-                        .split_whitespace()
-                        .last()
-                        .ok_or("test missing value".to_string())?
-                        .parse()
-                        .map_err(|e| format!("invalid true value {:?}", e))?;
+                    let target = parse_last(chunks.next(), "true")?;
+                    match tests.last_mut() {
+                        Some((_, t @ None)) => *t = Some(target),
+                        _ => return Err("If true without a test".to_string()),
+                    }
                 }
                 "If false" => {
-                    monkey.if_divisible_false = chunks
-                        .next()
-                        .ok_or("missing chunk for false".to_string())?
-                        .split_whitespace()
-                        .last()
-                        .ok_or("test missing value".to_string())?
-                        .parse()
-                        .map_err(|e| format!("invalid false value {:?}", e))?;
+                    if_all_false = Some(parse_last(chunks.next(), "false")?);
                 }
                 s => return Err(format!("unrecognized chunk: {s}")),
             }
         }
 
-        Ok(monkey)
+        Ok(Monkey {
+            items,
+            total_inspections: 0,
+            operation: operation.ok_or("missing operation".to_string())?,
+            tests: tests
+                .into_iter()
+                .map(|(divisor, target)| {
+                    target
+                        .map(|target| Test { divisor, target })
+                        .ok_or(format!("missing If true for divisible by {divisor}"))
+                })
+                .collect::<Result<_, _>>()?,
+            if_all_false: if_all_false.ok_or("missing If false".to_string())?,
+            relief,
+        })
+    }
+}
+
+#[test]
+fn simulate_works() {
+    let input = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    assert_eq!(main(input), (10605, 2713310158));
+
+    let mut input = "\
+Monkey 0:
+  Starting items: 3, 8, 4, 5
+  Operation: new = old + 1
+  Relief: old
+  Test: divisible by 2
+    If true: throw to monkey 1
+  Test: divisible by 3
+    If true: throw to monkey 2
+    If false: throw to monkey 3"
+        .to_string();
+    for i in 1..=3 {
+        input.push_str(&format!(
+            "
+
+Monkey {i}:
+  Starting items:
+  Operation: new = old
+  Test: divisible by 7
+    If true: throw to monkey {i}
+    If false: throw to monkey {i}"
+        ));
     }
+
+    let monkeys = simulate(
+        parse::<u64>(&input).unwrap(),
+        1,
+        &Relief::from_str("old / 2").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(monkeys[0].total_inspections, 4);
+    assert_eq!(monkeys[1].items, vec![2, 3]);
+    assert_eq!(monkeys[2].items, vec![4]);
+    assert_eq!(monkeys[3].items, vec![2]);
+
+    let (_, trace) = simulate_traced(
+        parse::<u64>(&input).unwrap(),
+        2,
+        &Relief::from_str("old / 2").unwrap(),
+        TraceOptions {
+            paths: true,
+            inspections: true,
+        },
+    )
+    .unwrap();
+    assert_eq!(trace.inspections, vec![vec![4, 2, 1, 1], vec![0, 2, 1, 1]]);
+    assert_eq!(
+        trace.paths[2],
+        vec![
+            Throw {
+                round: 1,
+                from: 0,
+                to: 3,
+                worry_level: 5
+            },
+            Throw {
+                round: 1,
+                from: 3,
+                to: 3,
+                worry_level: 2
+            },
+            Throw {
+                round: 2,
+                from: 3,
+                to: 3,
+                worry_level: 1
+            },
+        ]
+    );
+    assert_eq!(
+        trace.histogram(1).unwrap(),
+        "Monkey 0 | #### 4\nMonkey 1 | ## 2\nMonkey 2 | # 1\nMonkey 3 | # 1\n"
+    );
+
+    // Monkey 0's divisors share none of the others', but the others share
+    // all of theirs.
+    assert_eq!(common_multiple(&parse::<u64>(&input).unwrap()), Ok(42));
+
+    let huge = parse::<u64>(
+        "\
+Monkey 0:
+  Starting items: 1
+  Operation: new = old
+  Test: divisible by 4294967311
+    If true: throw to monkey 0
+  Test: divisible by 4294967357
+    If true: throw to monkey 0
+    If false: throw to monkey 0",
+    )
+    .unwrap();
+    assert!(simulate(huge.clone(), 1, &Relief::CommonMultiple).is_err());
+    assert!(simulate(huge, 1, &Relief::from_str("old / 3").unwrap()).is_ok());
+
+    assert!(
+        parse::<u64>("Monkey 0:\n  Operation: new = old\n  If false: throw to monkey 1").is_err()
+    );
+    assert_eq!(
+        Expression::from_str("new = old * old + 3")
+            .unwrap()
+            .evaluate(&2u64),
+        Some(7)
+    );
+    assert!(Expression::from_str("old / 0").is_err());
+    assert_eq!(
+        Expression::from_str("old - 3").unwrap().evaluate(&2u64),
+        None
+    );
+
+    // Squaring every worry level soon outgrows a u64.
+    let overflowing = simulate(
+        parse::<u64>(&input).unwrap(),
+        100,
+        &Relief::from_str("old * old").unwrap(),
+    );
+    assert!(overflowing.is_err());
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn bigint_works() {
+    let input = "\
+Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    let params = Params {
+        bigint: true,
+        ..Params::default()
+    };
+    assert_eq!(main_with_params(input, &params), (10605, 2713310158));
+
+    // Without any relief the worry levels quickly outgrow a u64, but they
+    // should still be thrown exactly as they are when kept modulo the
+    // common multiple.
+    let inspections = |monkeys: &[Monkey<BigUint>]| -> Vec<usize> {
+        monkeys.iter().map(|m| m.total_inspections).collect()
+    };
+    let faithful = simulate(
+        parse::<BigUint>(input).unwrap(),
+        20,
+        &Relief::from_str("old").unwrap(),
+    )
+    .unwrap();
+    let modular = simulate(
+        parse::<BigUint>(input).unwrap(),
+        20,
+        &Relief::CommonMultiple,
+    )
+    .unwrap();
+    assert_eq!(inspections(&faithful), inspections(&modular));
+    assert_eq!(inspections(&faithful), vec![99, 97, 8, 103]);
+    assert!(faithful
+        .iter()
+        .flat_map(|m| &m.items)
+        .any(|w| w.bits() > 64));
 }
@@ -1,8 +1,121 @@
-use std::fmt::{Debug, Display};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::{Debug, Display},
+};
 
-use crate::coord::Coord;
+use crate::{
+    coord::Coord,
+    params::{parse_param, unknown_param, Parameters},
+};
+
+/// The rules for getting around the hill.
+///
+/// Every step costs 1, plus `climb_cost` for each letter of height it climbs
+/// and `descent_cost` for each letter it descends.
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub max_climb: i32,
+    pub max_descent: i32,
+    pub climb_cost: u32,
+    pub descent_cost: u32,
+    pub start: char,
+    pub start_height: char,
+    pub end: char,
+    pub end_height: char,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            max_climb: 1,
+            max_descent: i32::MAX,
+            climb_cost: 0,
+            descent_cost: 0,
+            start: 'S',
+            start_height: 'a',
+            end: 'E',
+            end_height: 'z',
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "max_climb" => self.max_climb = parse_param(key, value)?,
+            "max_descent" => self.max_descent = parse_param(key, value)?,
+            "climb_cost" => self.climb_cost = parse_param(key, value)?,
+            "descent_cost" => self.descent_cost = parse_param(key, value)?,
+            "start" => self.start = parse_param(key, value)?,
+            "start_height" => self.start_height = parse_param(key, value)?,
+            "end" => self.end = parse_param(key, value)?,
+            "end_height" => self.end_height = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
+
+impl Params {
+    fn elevation(&self, c: char) -> char {
+        if c == self.start {
+            self.start_height
+        } else if c == self.end {
+            self.end_height
+        } else {
+            c
+        }
+    }
+
+    /// What stepping from a square at height `from` to one at height `to`
+    /// costs, if it's allowed at all.
+    fn step_cost(&self, from: char, to: char) -> Option<u32> {
+        let change = to as i32 - from as i32;
+        if change > self.max_climb || -change > self.max_descent {
+            return None;
+        }
+
+        Some(
+            1 + self.climb_cost * change.max(0) as u32
+                + self.descent_cost * (-change).max(0) as u32,
+        )
+    }
+}
 
 pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
+    let climb = climb(input, params);
+
+    if cfg!(feature = "debug") {
+        eprintln!("{}", climb.height_map.with_path(&climb.from_start.path));
+    }
+
+    (climb.from_start.cost, climb.from_lowest.cost)
+}
+
+/// The answers to both parts, along with the routes that give them.
+pub struct Climb {
+    pub height_map: TopMap<char>,
+    pub descent: Descent,
+    /// The cheapest route from the start square, for part 1.
+    pub from_start: Route,
+    /// The cheapest route from any of the lowest squares, for part 2.
+    pub from_lowest: Route,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub cost: u32,
+    /// Every square along the route, including both ends.
+    pub path: Vec<Coord<usize>>,
+}
+
+pub fn climb(input: &str, params: &Params) -> Climb {
     let mut start: Option<Coord<usize>> = None;
     let mut end: Option<Coord<usize>> = None;
     let mut lowest: Vec<Coord<usize>> = Vec::new();
@@ -14,13 +127,13 @@ pub fn main(input: &str) -> (u32, u32) {
             line.chars()
                 .enumerate()
                 .map(|(x, c)| {
-                    if c == 'S' {
+                    if c == params.start {
                         start = Some(Coord(x, y));
-                    } else if c == 'E' {
+                    } else if c == params.end {
                         end = Some(Coord(x, y));
                     }
 
-                    if c == 'S' || c == 'a' {
+                    if params.elevation(c) == params.start_height {
                         lowest.push(Coord(x, y));
                     }
 
@@ -33,110 +146,96 @@ pub fn main(input: &str) -> (u32, u32) {
     let start = start.unwrap();
     let end = end.unwrap();
 
-    let dist_map: TopMap<Option<u32>> = TopMap {
-        coords: vec![vec![None; elevations[0].len()]; elevations.len()],
-    };
     let height_map = TopMap { coords: elevations };
+    let descent = Descent::search(&height_map, end, params);
 
-    (
-        {
-            let mut new_dist_map = dist_map.clone();
-            new_dist_map.set_pos(start, Some(0));
-            spread(&height_map, &mut new_dist_map, start);
-            new_dist_map.get_pos(end).unwrap()
-        },
-        {
-            let mut min = u32::MAX;
-            for c in lowest {
-                let mut new_dist_map = dist_map.clone();
-                new_dist_map.set_pos(c, Some(0));
-                spread(&height_map, &mut new_dist_map, c);
-                match new_dist_map.get_pos(end) {
-                    Some(steps) => {
-                        if steps < min {
-                            min = steps;
-                        }
-                    }
-                    None => {}
+    let from_start = descent.route(start).unwrap();
+    let from_lowest = lowest
+        .into_iter()
+        .filter_map(|c| descent.route(c))
+        .min_by_key(|route| route.cost)
+        .unwrap();
+
+    Climb {
+        height_map,
+        descent,
+        from_start,
+        from_lowest,
+    }
+}
+
+/// Every square's cheapest route to the summit, found with a single search
+/// walking backwards from it, which answers both parts at once.
+///
+/// With the default rules every step costs the same and this is just a
+/// breadth-first search, but Dijkstra's algorithm keeps it right when steps
+/// are weighted by how much they climb or descend.
+pub struct Descent {
+    dist_map: TopMap<Option<u32>>,
+    /// The square to step to next to get closer to the summit.
+    next_map: TopMap<Option<Coord<usize>>>,
+}
+
+impl Descent {
+    fn search(height_map: &TopMap<char>, end: Coord<usize>, params: &Params) -> Self {
+        let mut dist_map = height_map.map(|_| None);
+        let mut next_map = height_map.map(|_| None);
+
+        dist_map.set_pos(end, Some(0));
+        let mut queue = BinaryHeap::from([(Reverse(0), end)]);
+
+        while let Some((Reverse(current_dist), pos)) = queue.pop() {
+            if dist_map.get_pos(pos) != Some(current_dist) {
+                continue;
+            }
+
+            let current_height = params.elevation(height_map.get_pos(pos));
+
+            for prev in height_map.neighbors(pos) {
+                let height = params.elevation(height_map.get_pos(prev));
+                let Some(cost) = params.step_cost(height, current_height) else {
+                    continue;
+                };
+
+                let dist = current_dist + cost;
+                if dist_map
+                    .get_pos(prev)
+                    .is_none_or(|prev_dist| dist < prev_dist)
+                {
+                    dist_map.set_pos(prev, Some(dist));
+                    next_map.set_pos(prev, Some(pos));
+                    queue.push((Reverse(dist), prev));
                 }
             }
-            min
-        },
-    )
-}
+        }
 
-fn spread(height_map: &TopMap<char>, dist_map: &mut TopMap<Option<u32>>, pos: Coord<usize>) {
-    let current_height = match height_map.get_pos(pos) {
-        'S' => 'a',
-        'E' => 'z',
-        c => c,
-    };
-    let current_dist = dist_map.get_pos(pos).unwrap();
-
-    if pos.1 > 0 {
-        check_coord(
-            height_map,
-            dist_map,
-            current_height,
-            current_dist,
-            Coord(pos.0, pos.1 - 1),
-        );
+        Descent { dist_map, next_map }
     }
 
-    if pos.1 < height_map.coords.len() - 1 {
-        check_coord(
-            height_map,
-            dist_map,
-            current_height,
-            current_dist,
-            Coord(pos.0, pos.1 + 1),
-        );
-    }
+    /// The squares visited on the shortest route from `start` to the summit,
+    /// including both ends.
+    pub fn path(&self, start: Coord<usize>) -> Option<Vec<Coord<usize>>> {
+        self.dist_map.get_pos(start)?;
 
-    if pos.0 > 0 {
-        check_coord(
-            height_map,
-            dist_map,
-            current_height,
-            current_dist,
-            Coord(pos.0 - 1, pos.1),
-        );
-    }
+        let mut path = vec![start];
+        while let Some(next) = self.next_map.get_pos(*path.last().unwrap()) {
+            path.push(next);
+        }
 
-    if pos.0 < dist_map.coords[pos.1].len() - 1 {
-        check_coord(
-            height_map,
-            dist_map,
-            current_height,
-            current_dist,
-            Coord(pos.0 + 1, pos.1),
-        );
+        Some(path)
     }
-}
 
-fn check_coord(
-    height_map: &TopMap<char>,
-    dist_map: &mut TopMap<Option<u32>>,
-    current_height: char,
-    current_dist: u32,
-    new_coord: Coord<usize>,
-) {
-    let height = match height_map.get_pos(new_coord) {
-        'S' => 'a',
-        'E' => 'z',
-        c => c,
-    };
-    if (height as i32 - current_height as i32) <= 1 {
-        let prev_dist = dist_map.get_pos(new_coord);
-        if prev_dist.is_none() || prev_dist.unwrap() > current_dist + 1 {
-            dist_map.set_pos(new_coord, Some(current_dist + 1));
-            spread(height_map, dist_map, new_coord);
-        }
+    /// The cost and squares of the shortest route from `start` to the summit.
+    pub fn route(&self, start: Coord<usize>) -> Option<Route> {
+        Some(Route {
+            cost: self.dist_map.get_pos(start)?,
+            path: self.path(start)?,
+        })
     }
 }
 
 #[derive(Clone)]
-struct TopMap<T: Clone> {
+pub struct TopMap<T: Clone> {
     coords: Vec<Vec<T>>,
 }
 
@@ -148,6 +247,53 @@ impl<T: Copy> TopMap<T> {
     fn set_pos(&mut self, pos: Coord<usize>, val: T) {
         self.coords[pos.1][pos.0] = val;
     }
+
+    fn map<U: Clone>(&self, f: impl Fn(T) -> U) -> TopMap<U> {
+        TopMap {
+            coords: self
+                .coords
+                .iter()
+                .map(|row| row.iter().map(|val| f(*val)).collect())
+                .collect(),
+        }
+    }
+
+    fn neighbors(&self, pos: Coord<usize>) -> impl Iterator<Item = Coord<usize>> {
+        let height = self.coords.len();
+        let width = self.coords[pos.1].len();
+
+        [
+            (pos.1 > 0).then(|| Coord(pos.0, pos.1 - 1)),
+            (pos.1 < height - 1).then(|| Coord(pos.0, pos.1 + 1)),
+            (pos.0 > 0).then(|| Coord(pos.0 - 1, pos.1)),
+            (pos.0 < width - 1).then(|| Coord(pos.0 + 1, pos.1)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl TopMap<char> {
+    /// Draws `path` over the map, with an arrow on each square pointing to the
+    /// next one.
+    pub fn with_path(&self, path: &[Coord<usize>]) -> TopMap<char> {
+        let mut map = self.clone();
+        for step in path.windows(2) {
+            let (from, to) = (step[0], step[1]);
+            let arrow = if to.1 < from.1 {
+                '^'
+            } else if to.1 > from.1 {
+                'v'
+            } else if to.0 < from.0 {
+                '<'
+            } else {
+                '>'
+            };
+            map.set_pos(from, arrow);
+        }
+
+        map
+    }
 }
 
 impl<T: Display + Clone> Display for TopMap<T> {
@@ -175,3 +321,48 @@ impl<T: Debug + Clone> Debug for TopMap<T> {
         Ok(())
     }
 }
+
+#[test]
+fn path_works() {
+    let input = "\
+Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+
+    assert_eq!(main(input), (31, 29));
+
+    // Every route has to climb 25 letters in total, and the shortest ones
+    // never descend.
+    let params = Params {
+        climb_cost: 1,
+        ..Params::default()
+    };
+    assert_eq!(main_with_params(input, &params), (56, 54));
+
+    let params = Params {
+        max_climb: 2,
+        ..Params::default()
+    };
+    assert_eq!(main_with_params(input, &params), (27, 23));
+
+    let climb = climb(input, &Params::default());
+    assert_eq!(climb.from_lowest.cost, 29);
+    assert_eq!(climb.from_lowest.path.len(), 30);
+    let path = climb.from_start.path;
+
+    assert_eq!(path.len(), 32);
+    assert_eq!(path.last(), Some(&Coord(5, 2)));
+    for step in path.windows(2) {
+        assert_eq!(
+            step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1),
+            1
+        );
+    }
+
+    let rendered = climb.height_map.with_path(&path).to_string();
+    assert_eq!(rendered.chars().filter(|c| "^v<>".contains(*c)).count(), 31);
+    assert!(!rendered.contains('S'), "{rendered}");
+    assert!(rendered.contains('E'), "{rendered}");
+}
@@ -1,19 +1,23 @@
-use std::{cmp::Ordering, iter::Peekable, str::FromStr};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+const DIVIDERS: [&str; 2] = ["[[2]]", "[[6]]"];
 
 pub fn main(input: &str) -> (u32, u32) {
     let mut sum = 0;
-    let dividers = vec![
-        PacketPiece::List(vec![PacketPiece::Number(2)]),
-        PacketPiece::List(vec![PacketPiece::Number(6)]),
-    ];
-    let mut packets: Vec<PacketPiece> = dividers.clone();
+    let mut packets: Vec<&str> = DIVIDERS.to_vec();
     for (i, pair) in input.split("\n\n").enumerate() {
         let index = i + 1;
         let mut lines = pair.lines();
-        let left = PacketPiece::from_str(lines.next().unwrap()).unwrap();
-        let right = PacketPiece::from_str(lines.next().unwrap()).unwrap();
+        let left = lines.next().unwrap();
+        let right = lines.next().unwrap();
 
-        if left <= right {
+        if compare_packets(left, right).unwrap() != Ordering::Greater {
             sum += index;
         }
         packets.push(left);
@@ -22,9 +26,9 @@ pub fn main(input: &str) -> (u32, u32) {
 
     let mut decoder_key = 1;
 
-    packets.sort();
+    packets.sort_by(|a, b| compare_packets(a, b).unwrap());
     for (i, packet) in packets.iter().enumerate() {
-        if dividers.contains(packet) {
+        if DIVIDERS.contains(packet) {
             decoder_key *= i + 1;
         }
     }
@@ -32,41 +36,467 @@ pub fn main(input: &str) -> (u32, u32) {
     (sum as _, decoder_key as _)
 }
 
+/// A packet, or a piece of one.
+///
+/// Packets are written the same way as JSON arrays of non-negative integers,
+/// and with the `serde` feature they can be (de)serialized as such.
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum PacketPiece {
+pub enum PacketPiece {
     List(Vec<PacketPiece>),
     Number(u32),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset the error was found at.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "position {}: {}", self.position, self.message)
+    }
+}
+
+/// A recursive-descent parser over a packet's bytes.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Parser {
+            bytes: s.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            position: self.pos,
+            message: message.into(),
+        })
+    }
+
+    /// The next non-whitespace byte, without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn unexpected<T>(&mut self, expected: &str) -> Result<T, ParseError> {
+        match self.peek() {
+            Some(c) => self.error(format!("expected {expected}, found {:?}", c as char)),
+            None => self.error(format!("expected {expected}, found end of input")),
+        }
+    }
+
+    fn packet(&mut self) -> Result<PacketPiece, ParseError> {
+        let piece = self.piece()?;
+        if self.peek().is_some() {
+            return self.unexpected("end of input");
+        }
+
+        Ok(piece)
+    }
+
+    fn piece(&mut self) -> Result<PacketPiece, ParseError> {
+        match self.peek() {
+            Some(b'[') => self.list(),
+            Some(b'0'..=b'9') => self.number(),
+            _ => self.unexpected("[ or a number"),
+        }
+    }
+
+    fn list(&mut self) -> Result<PacketPiece, ParseError> {
+        self.pos += 1;
+
+        let mut list = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(PacketPiece::List(list));
+        }
+
+        loop {
+            list.push(self.piece()?);
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(PacketPiece::List(list));
+                }
+                _ => return self.unexpected(", or ]"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<PacketPiece, ParseError> {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+
+        // Only ASCII digits were consumed, so this is always valid UTF-8.
+        let digits = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+        match digits.parse() {
+            Ok(val) => Ok(PacketPiece::Number(val)),
+            Err(_) => {
+                self.pos = start;
+                self.error(format!("number out of range: {digits}"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Number(u32),
+}
+
+/// Reads a packet's tokens straight out of its text.
+///
+/// A number that's being compared against a list gets wrapped in a list of
+/// its own by `wrap`ping it: it's treated as if the list it's compared with
+/// had opened around it too, and the matching closes are emitted right after
+/// it, so no promoted list is ever built.
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    wraps: usize,
+    closes: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(s: &'a str) -> Self {
+        Tokens {
+            bytes: s.as_bytes(),
+            pos: 0,
+            wraps: 0,
+            closes: 0,
+        }
+    }
+
+    /// The next token along with how many bytes it takes up.
+    fn peek(&mut self) -> Result<Option<(Token, usize)>, ParseError> {
+        if self.closes > 0 {
+            return Ok(Some((Token::Close, 0)));
+        }
+
+        while matches!(self.bytes.get(self.pos), Some(b',' | b' ')) {
+            self.pos += 1;
+        }
+
+        let Some(byte) = self.bytes.get(self.pos) else {
+            return Ok(None);
+        };
+
+        Ok(match byte {
+            b'[' => Some((Token::Open, 1)),
+            b']' => Some((Token::Close, 1)),
+            b'0'..=b'9' => Some(self.number()?),
+            _ => None,
+        })
+    }
+
+    fn number(&self) -> Result<(Token, usize), ParseError> {
+        let digits = &self.bytes[self.pos..];
+        let len = digits
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(digits.len());
+        let digits = &digits[..len];
+
+        let val = digits.iter().try_fold(0u32, |val, digit| {
+            val.checked_mul(10)?.checked_add((digit - b'0') as u32)
+        });
+
+        match val {
+            Some(val) => Ok((Token::Number(val), len)),
+            None => Err(ParseError {
+                position: self.pos,
+                // Only ASCII digits were taken, so this is always valid UTF-8.
+                message: format!(
+                    "number out of range: {}",
+                    std::str::from_utf8(digits).unwrap()
+                ),
+            }),
+        }
+    }
+
+    fn advance(&mut self, (token, len): (Token, usize)) {
+        if self.closes > 0 {
+            self.closes -= 1;
+            return;
+        }
+
+        self.pos += len;
+        if let Token::Number(_) = token {
+            self.closes = self.wraps;
+            self.wraps = 0;
+        }
+    }
+}
+
+/// Compares two packets straight from their text, without parsing them into
+/// [`PacketPiece`]s first or allocating anything.
+///
+/// Both packets are assumed to be well formed; anything that isn't part of a
+/// packet ends it early. A number too big to compare is reported with its
+/// position, the same way [`PacketPiece::from_str`] reports it.
+pub fn compare_packets(left: &str, right: &str) -> Result<Ordering, ParseError> {
+    let mut left = Tokens::new(left);
+    let mut right = Tokens::new(right);
+
+    loop {
+        let (l, r) = match (left.peek()?, right.peek()?) {
+            (None, None) => return Ok(Ordering::Equal),
+            (None, Some(_)) => return Ok(Ordering::Less),
+            (Some(_), None) => return Ok(Ordering::Greater),
+            (Some(l), Some(r)) => (l, r),
+        };
+
+        match (l.0, r.0) {
+            (Token::Number(lhs), Token::Number(rhs)) if lhs != rhs => return Ok(lhs.cmp(&rhs)),
+            (Token::Close, Token::Close)
+            | (Token::Open, Token::Open)
+            | (Token::Number(_), Token::Number(_)) => {
+                left.advance(l);
+                right.advance(r);
+            }
+            (Token::Close, _) => return Ok(Ordering::Less),
+            (_, Token::Close) => return Ok(Ordering::Greater),
+            (Token::Number(_), Token::Open) => {
+                left.wraps += 1;
+                right.advance(r);
+            }
+            (Token::Open, Token::Number(_)) => {
+                right.wraps += 1;
+                left.advance(l);
+            }
+        }
+    }
+}
+
+/// Explains how each pair of packets compares, in the same format as the
+/// puzzle's worked example.
+pub fn explain(input: &str) -> String {
+    input
+        .split("\n\n")
+        .enumerate()
+        .map(|(i, pair)| {
+            let mut lines = pair.lines();
+            let left = PacketPiece::from_str(lines.next().unwrap()).unwrap();
+            let right = PacketPiece::from_str(lines.next().unwrap()).unwrap();
+
+            format!("== Pair {} ==\n{}\n", i + 1, left.explain_cmp(&right).1)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// One step taken while comparing two packets. `depth` is how deeply nested
+/// the step is, for indenting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Two pieces are compared. `path` holds the index into each enclosing
+    /// list that led to them.
+    Compare {
+        depth: usize,
+        path: Vec<usize>,
+        left: PacketPiece,
+        right: PacketPiece,
+    },
+    /// A number on one side was wrapped in a list to compare it with a list.
+    Promote {
+        depth: usize,
+        side: Side,
+        value: u32,
+    },
+    /// One side's list ran out of items first.
+    RanOut { depth: usize, side: Side },
+    /// One side's number was smaller.
+    Smaller { depth: usize, side: Side },
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = |side: &Side| match side {
+            Side::Left => "so inputs are in the right order",
+            Side::Right => "so inputs are not in the right order",
+        };
+
+        match self {
+            Step::Compare {
+                depth, left, right, ..
+            } => write!(f, "{}- Compare {left} vs {right}", "  ".repeat(*depth)),
+            Step::Promote { depth, side, value } => write!(
+                f,
+                "{}- Mixed types; convert {side} to [{value}] and retry comparison",
+                "  ".repeat(*depth)
+            ),
+            Step::RanOut { depth, side } => write!(
+                f,
+                "{}- {} side ran out of items, {}",
+                "  ".repeat(*depth),
+                capitalize(side),
+                verdict(side)
+            ),
+            Step::Smaller { depth, side } => write!(
+                f,
+                "{}- {} side is smaller, {}",
+                "  ".repeat(*depth),
+                capitalize(side),
+                verdict(side)
+            ),
+        }
+    }
+}
+
+fn capitalize(side: &Side) -> &'static str {
+    match side {
+        Side::Left => "Left",
+        Side::Right => "Right",
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Explanation {
+    pub steps: Vec<Step>,
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+
+        Ok(())
+    }
+}
+
 impl PacketPiece {
-    fn from_tokens<'a, I>(tokens: &mut Peekable<I>) -> Result<Self, String>
-    where
-        I: Iterator<Item = &'a str>,
-    {
-        let first = tokens.next().unwrap();
-
-        match first {
-            "[" => {
-                let mut list: Vec<PacketPiece> = Vec::new();
-
-                loop {
-                    if tokens.peek() == Some(&"") {
-                        let _ = tokens.next();
-                    }
+    /// Compares two packets like [`Ord::cmp`], also recording each step it
+    /// took to decide.
+    pub fn explain_cmp(&self, other: &Self) -> (Ordering, Explanation) {
+        let mut explanation = Explanation::default();
+        let ordering = self.cmp_explained(other, 0, &mut Vec::new(), &mut explanation.steps);
+
+        (ordering, explanation)
+    }
+
+    fn cmp_explained(
+        &self,
+        other: &Self,
+        depth: usize,
+        path: &mut Vec<usize>,
+        steps: &mut Vec<Step>,
+    ) -> Ordering {
+        steps.push(Step::Compare {
+            depth,
+            path: path.clone(),
+            left: self.clone(),
+            right: other.clone(),
+        });
+
+        match (self, other) {
+            (PacketPiece::Number(lhs), PacketPiece::Number(rhs)) => {
+                let ordering = lhs.cmp(rhs);
+                let side = match ordering {
+                    Ordering::Less => Some(Side::Left),
+                    Ordering::Greater => Some(Side::Right),
+                    Ordering::Equal => None,
+                };
+                if let Some(side) = side {
+                    steps.push(Step::Smaller {
+                        depth: depth + 1,
+                        side,
+                    });
+                }
+
+                ordering
+            }
+            (PacketPiece::List(lhs), PacketPiece::List(rhs)) => {
+                for i in 0..lhs.len().max(rhs.len()) {
+                    let (Some(l), Some(r)) = (lhs.get(i), rhs.get(i)) else {
+                        let side = if i >= lhs.len() {
+                            Side::Left
+                        } else {
+                            Side::Right
+                        };
+                        steps.push(Step::RanOut {
+                            depth: depth + 1,
+                            side,
+                        });
+
+                        return if side == Side::Left {
+                            Ordering::Less
+                        } else {
+                            Ordering::Greater
+                        };
+                    };
 
-                    if *tokens.peek().unwrap() == "]" {
-                        let _ = tokens.next();
-                        break;
-                    } else {
-                        list.push(PacketPiece::from_tokens(tokens)?);
+                    path.push(i);
+                    let ordering = l.cmp_explained(r, depth + 1, path, steps);
+                    path.pop();
+
+                    if ordering != Ordering::Equal {
+                        return ordering;
                     }
                 }
 
-                Ok(PacketPiece::List(list))
+                Ordering::Equal
+            }
+            (PacketPiece::Number(lhs), PacketPiece::List(_)) => {
+                steps.push(Step::Promote {
+                    depth: depth + 1,
+                    side: Side::Left,
+                    value: *lhs,
+                });
+                PacketPiece::List(vec![PacketPiece::Number(*lhs)]).cmp_explained(
+                    other,
+                    depth + 1,
+                    path,
+                    steps,
+                )
             }
-            c => {
-                let val: u32 = c.parse().map_err(|_| format!("invalid value: {c}"))?;
-                Ok(PacketPiece::Number(val))
+            (PacketPiece::List(_), PacketPiece::Number(rhs)) => {
+                steps.push(Step::Promote {
+                    depth: depth + 1,
+                    side: Side::Right,
+                    value: *rhs,
+                });
+                self.cmp_explained(
+                    &PacketPiece::List(vec![PacketPiece::Number(*rhs)]),
+                    depth + 1,
+                    path,
+                    steps,
+                )
             }
         }
     }
@@ -121,13 +551,211 @@ impl PartialOrd for PacketPiece {
 }
 
 impl FromStr for PacketPiece {
-    type Err = String;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // very hacky way to tokenize, probably a better way
-        let new = s.replace("[", "[,").replace("]", ",]");
-        let mut tokens = new.split(',').peekable();
+        Parser::new(s).packet()
+    }
+}
+
+impl Display for PacketPiece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketPiece::Number(val) => write!(f, "{val}"),
+            PacketPiece::List(list) => {
+                write!(f, "[")?;
+                for (i, piece) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{piece}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
 
-        PacketPiece::from_tokens(&mut tokens)
+#[cfg(feature = "serde")]
+impl Serialize for PacketPiece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            PacketPiece::Number(val) => serializer.serialize_u32(*val),
+            PacketPiece::List(list) => list.serialize(serializer),
+        }
     }
 }
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for PacketPiece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PacketVisitor;
+
+        impl<'de> Visitor<'de> for PacketVisitor {
+            type Value = PacketPiece;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a non-negative integer or a list of packets")
+            }
+
+            fn visit_u64<E: de::Error>(self, val: u64) -> Result<Self::Value, E> {
+                u32::try_from(val)
+                    .map(PacketPiece::Number)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(val), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, val: i64) -> Result<Self::Value, E> {
+                u32::try_from(val)
+                    .map(PacketPiece::Number)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(val), &self))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut list = Vec::new();
+                while let Some(piece) = seq.next_element()? {
+                    list.push(piece);
+                }
+
+                Ok(PacketPiece::List(list))
+            }
+        }
+
+        deserializer.deserialize_any(PacketVisitor)
+    }
+}
+
+#[test]
+fn parser_works() {
+    for packet in [
+        "[]",
+        "[[[]]]",
+        "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+        "[[4,4],4,4,10]",
+    ] {
+        assert_eq!(PacketPiece::from_str(packet).unwrap().to_string(), packet);
+    }
+
+    assert_eq!(
+        PacketPiece::from_str(" [1, [ 2 ]] ").unwrap(),
+        PacketPiece::List(vec![
+            PacketPiece::Number(1),
+            PacketPiece::List(vec![PacketPiece::Number(2)])
+        ])
+    );
+
+    let error = |s: &str| PacketPiece::from_str(s).unwrap_err();
+    assert_eq!(error("[1,,2]").position, 3);
+    assert_eq!(
+        error("[1,2").to_string(),
+        "position 4: expected , or ], found end of input"
+    );
+    assert_eq!(
+        error("[1]]").to_string(),
+        "position 3: expected end of input, found ']'"
+    );
+    assert_eq!(error("[99999999999]").position, 1);
+    assert_eq!(error("").position, 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_works() {
+    let packet = PacketPiece::from_str("[1,[2,[3,[4,[5,6,0]]]],8,9]").unwrap();
+
+    let json = serde_json::to_string(&packet).unwrap();
+    assert_eq!(json, packet.to_string());
+    assert_eq!(serde_json::from_str::<PacketPiece>(&json).unwrap(), packet);
+
+    assert!(serde_json::from_str::<PacketPiece>("[1,-2]").is_err());
+    assert!(serde_json::from_str::<PacketPiece>("[\"1\"]").is_err());
+}
+
+#[test]
+fn explain_works() {
+    let left = PacketPiece::from_str("[[1],[2,3,4]]").unwrap();
+    let right = PacketPiece::from_str("[[1],4]").unwrap();
+    let (ordering, explanation) = left.explain_cmp(&right);
+
+    assert_eq!(ordering, Ordering::Less);
+    assert_eq!(
+        explanation.to_string(),
+        "\
+- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+"
+    );
+    assert!(matches!(
+        &explanation.steps[3],
+        Step::Compare { path, .. } if path == &vec![1]
+    ));
+
+    let (ordering, explanation) = PacketPiece::from_str("[7,7,7,7]")
+        .unwrap()
+        .explain_cmp(&PacketPiece::from_str("[7,7,7]").unwrap());
+    assert_eq!(ordering, Ordering::Greater);
+    assert_eq!(
+        explanation.steps.last(),
+        Some(&Step::RanOut {
+            depth: 1,
+            side: Side::Right
+        })
+    );
+}
+
+#[test]
+fn compare_packets_works() {
+    let packets = [
+        "[1,1,3,1,1]",
+        "[1,1,5,1,1]",
+        "[[1],[2,3,4]]",
+        "[[1],4]",
+        "[9]",
+        "[[8,7,6]]",
+        "[[4,4],4,4]",
+        "[[4,4],4,4,4]",
+        "[7,7,7,7]",
+        "[7,7,7]",
+        "[]",
+        "[3]",
+        "[[[]]]",
+        "[[]]",
+        "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+        "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+        "[1]",
+        "[[1]]",
+        "[[[1]],2]",
+        "[1,[]]",
+        "[[10]]",
+        "[[],1]",
+    ];
+
+    for left in packets {
+        for right in packets {
+            assert_eq!(
+                compare_packets(left, right),
+                Ok(PacketPiece::from_str(left)
+                    .unwrap()
+                    .cmp(&PacketPiece::from_str(right).unwrap())),
+                "{left} vs {right}"
+            );
+        }
+    }
+
+    // Numbers past u32::MAX are reported where they start, just like the
+    // parser does, rather than wrapping around.
+    let err = compare_packets("[1,99999999999]", "[1,2]").unwrap_err();
+    assert_eq!(err, PacketPiece::from_str("[1,99999999999]").unwrap_err());
+    assert_eq!(err.position, 3);
+    assert_eq!(err.message, "number out of range: 99999999999");
+    assert!(compare_packets("[0]", "[[4294967296]]").is_err());
+    assert_eq!(
+        compare_packets("[4294967295]", "[4294967294]"),
+        Ok(Ordering::Greater)
+    );
+}
@@ -1,120 +1,435 @@
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+pub mod cave;
 
-use crate::coord::Coord;
+use std::{fmt::Display, str::FromStr};
 
-const SAND_SPAWN: Coord<i32> = Coord(500, 0);
+use crate::{
+    coord::Coord,
+    image::{Canvas, Framed, Palette, Raster, Recorder, Rgb, TileMap},
+    params::{parse_param, unknown_param, Parameters},
+    range::Range,
+    visualize::Visualizer,
+};
 
-pub fn main(input: &str) -> (u32, u32) {
-    let mut grid = Grid::default();
-    grid.read_wall_lines(input);
+#[derive(Debug, Clone)]
+pub struct Params {
+    /// Where sand is poured in from, one grain from each in turn.
+    pub sources: Vec<Coord<i32>>,
+    /// Whether there's an infinitely wide floor under the cave. Without one,
+    /// sand that falls past the lowest rock is lost to the abyss.
+    pub floor: bool,
+    /// How far below the lowest rock the floor is.
+    pub floor_distance: i32,
+    pub fall_rule: FallRule,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            sources: vec![Coord(500, 0)],
+            floor: true,
+            floor_distance: 2,
+            fall_rule: FallRule::default(),
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "sources" => {
+                self.sources = value
+                    .split_whitespace()
+                    .map(|s| parse_param(key, s))
+                    .collect::<Result<_, _>>()?;
+            }
+            "floor" => self.floor = parse_param(key, value)?,
+            "floor_distance" => self.floor_distance = parse_param(key, value)?,
+            "fall_rule" => self.fall_rule = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.floor_distance < 1 {
+            return Err("floor_distance must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// The moves a grain of sand tries each step, in order of preference. The
+/// grain comes to rest once they're all blocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallRule {
+    moves: Vec<Coord<i32>>,
+}
+
+impl Default for FallRule {
+    fn default() -> Self {
+        FallRule {
+            moves: vec![Coord(0, 1), Coord(-1, 1), Coord(1, 1)],
+        }
+    }
+}
+
+impl FromStr for FallRule {
+    type Err = String;
+
+    /// Parses moves written as space separated `dx,dy` pairs, like
+    /// `0,1 -1,1 1,1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let moves: Vec<Coord<i32>> = s
+            .split_whitespace()
+            .map(Coord::from_str)
+            .collect::<Result<_, _>>()?;
+
+        if moves.is_empty() {
+            return Err("fall rule needs at least one move".to_string());
+        }
+        if let Some(m) = moves.iter().find(|m| m.1 < 1) {
+            return Err(format!("move {},{} doesn't fall", m.0, m.1));
+        }
+
+        Ok(FallRule { moves })
+    }
+}
+
+impl Display for FallRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, Coord(x, y)) in self.moves.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{x},{y}")?;
+        }
 
-    let max_y = grid.max_y().unwrap();
-    let max_x = grid.max_x().unwrap();
-    let min_x = grid.min_x().unwrap();
-    let floor_y = max_y + 2;
+        Ok(())
+    }
+}
 
-    let down_unit = Coord(0, 1);
-    let left_unit = Coord(-1, 0);
-    let right_unit = Coord(1, 0);
+pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
+    if !params.floor {
+        return simulate(input, params, |_| true);
+    }
 
-    for x in 0..1000 {
-        grid.coords.insert(Coord(x, floor_y), Tile::Wall);
+    // Only part 1 needs simulating grain by grain. With a floor, every square
+    // the sources can reach ends up filled, so part 2 can be counted directly.
+    let mut sand = Sand::new(input, params);
+    let mut p1_sands = 0;
+    while let Some(Outcome::Settled(grain)) = sand.pour() {
+        if grain.1 > sand.max_y {
+            break;
+        }
+        p1_sands += 1;
     }
 
+    (p1_sands, fill(&Sand::new(input, params)))
+}
+
+pub fn visualize(input: &str, params: &Params, visualizer: &mut Visualizer) {
+    simulate(input, params, |grid| visualizer.frame(grid));
+}
+
+pub fn export(input: &str, params: &Params, recorder: &mut Recorder) {
+    // Sand only ever piles up, so a first run finds how far it spreads and
+    // every frame can be drawn that size.
+    let mut frames = 0;
+    let mut bounds = None;
+    simulate(input, params, |grid| {
+        frames += 1;
+        bounds = Some(grid.bounds());
+        true
+    });
+
+    let Some((x, y)) = bounds else {
+        return;
+    };
+    recorder.expect_frames(frames);
+    simulate(input, params, |grid| {
+        recorder.frame(&Framed { state: grid, x, y })
+    });
+}
+
+/// Pours sand in until every source is blocked, returning how many grains
+/// came to rest before the first one fell below the lowest rock, and how many
+/// came to rest in total.
+fn simulate(input: &str, params: &Params, mut on_grain: impl FnMut(&Grid) -> bool) -> (u32, u32) {
+    let mut sand = Sand::new(input, params);
+
     let mut p1_sands = 0;
     let mut p2_sands = 0;
     let mut p1_over = false;
 
-    'sand_lifecycle: loop {
-        let mut sand = SAND_SPAWN;
-        loop {
-            if grid.coords.get(&(sand + down_unit)).is_none() {
-                sand = sand + down_unit;
-            } else if grid.coords.get(&(sand + down_unit + left_unit)).is_none() {
-                sand = sand + down_unit + left_unit;
-            } else if grid.coords.get(&(sand + down_unit + right_unit)).is_none() {
-                sand = sand + down_unit + right_unit;
-            } else {
-                grid.coords.insert(sand, Tile::Sand);
-
-                if sand.0 >= min_x && sand.0 <= max_x && sand.1 <= max_y && !p1_over {
-                    p1_sands += 1;
-                } else if !p1_over {
+    while let Some(outcome) = sand.pour() {
+        match outcome {
+            Outcome::Settled(grain) => {
+                if grain.1 > sand.max_y {
                     p1_over = true;
                 }
-
+                if !p1_over {
+                    p1_sands += 1;
+                }
                 p2_sands += 1;
 
-                if sand == SAND_SPAWN {
-                    break 'sand_lifecycle;
-                } else {
-                    continue 'sand_lifecycle;
+                if !on_grain(&sand.grid) {
+                    break;
                 }
             }
+            Outcome::Lost => p1_over = true,
         }
     }
 
     (p1_sands, p2_sands)
 }
 
+/// Counts the grains that come to rest with a floor, without simulating them.
+///
+/// A grain only settles once every move from its square is blocked, so once
+/// the sources are covered, every square reachable from them is sand. That
+/// means the cave can be filled in a row at a time from the top.
+fn fill(sand: &Sand) -> u32 {
+    let grid = &sand.grid;
+    let mut filled = vec![false; grid.tiles.len()];
+    for source in &sand.sources {
+        if let Some(i) = grid.index(*source).filter(|_| !grid.is_blocked(*source)) {
+            filled[i] = true;
+        }
+    }
+
+    let mut count = 0;
+    // Moves always go down, so by the time a row is reached, everything that
+    // can spill into it already has.
+    for i in 0..filled.len() {
+        if !filled[i] {
+            continue;
+        }
+        count += 1;
+
+        let pos = grid.coord(i);
+        for m in &sand.moves {
+            let next = pos + *m;
+            if let Some(j) = grid.index(next).filter(|_| !grid.is_blocked(next)) {
+                filled[j] = true;
+            }
+        }
+    }
+
+    count
+}
+
+enum Outcome {
+    /// A grain came to rest at the given position.
+    Settled(Coord<i32>),
+    /// A grain fell into the abyss.
+    Lost,
+}
+
+/// The state of the cave as sand is poured into it.
+///
+/// Rather than falling all the way from its source, each grain picks up where
+/// the previous one from the same source came to rest, at the square it fell
+/// from. Every move it made to get there was forced, so the new grain would
+/// have made exactly the same ones.
+struct Sand {
+    grid: Grid,
+    max_y: i32,
+    moves: Vec<Coord<i32>>,
+    sources: Vec<Coord<i32>>,
+    /// The path the last grain from each source took, or `None` once the
+    /// source has lost a grain to the abyss.
+    paths: Vec<Option<Vec<Coord<i32>>>>,
+    next_source: usize,
+}
+
+impl Sand {
+    fn new(input: &str, params: &Params) -> Self {
+        let walls: Vec<Coord<i32>> = cave::parse_scan(input)
+            .unwrap_or_else(|e| panic!("{e}"))
+            .iter()
+            .flat_map(cave::RockPath::tiles)
+            .collect();
+        let max_y = walls.iter().map(|Coord(_, y)| *y).max().unwrap();
+        let floor_y = params.floor.then_some(max_y + params.floor_distance);
+
+        // Size the grid to cover everywhere sand could possibly get to.
+        let moves = params.fall_rule.moves.clone();
+        let max_dx = moves.iter().map(|m| m.0.abs()).max().unwrap_or(0);
+        let max_dy = moves.iter().map(|m| m.1).max().unwrap_or(1);
+        let points = || walls.iter().chain(&params.sources);
+        let top = points().map(|Coord(_, y)| *y).min().unwrap().min(0);
+        let bottom = floor_y.unwrap_or(max_y + max_dy);
+        let reach = max_dx * (bottom - top);
+        let min = Coord(points().map(|Coord(x, _)| *x).min().unwrap() - reach, top);
+        let max = Coord(
+            points().map(|Coord(x, _)| *x).max().unwrap() + reach,
+            bottom,
+        );
+
+        let mut grid = Grid::new(min, max, floor_y);
+        for wall in walls {
+            grid.insert(wall, Tile::Wall);
+        }
+
+        Sand {
+            grid,
+            max_y,
+            moves,
+            sources: params.sources.clone(),
+            paths: params
+                .sources
+                .iter()
+                .map(|source| Some(vec![*source]))
+                .collect(),
+            next_source: 0,
+        }
+    }
+
+    /// Drops a grain from the next source that isn't blocked or lost to the
+    /// abyss, or returns `None` if there aren't any left.
+    fn pour(&mut self) -> Option<Outcome> {
+        let i = (0..self.sources.len())
+            .map(|offset| (self.next_source + offset) % self.sources.len())
+            .find(|i| self.paths[*i].is_some() && !self.grid.is_blocked(self.sources[*i]))?;
+        self.next_source = (i + 1) % self.sources.len();
+
+        let path = self.paths[i].as_mut().unwrap();
+        if path.is_empty() {
+            path.push(self.sources[i]);
+        }
+
+        loop {
+            let sand = *path.last().unwrap();
+            let next = self
+                .moves
+                .iter()
+                .map(|m| sand + *m)
+                .find(|next| !self.grid.is_blocked(*next));
+
+            match next {
+                Some(next) if self.grid.floor_y.is_none() && next.1 > self.max_y => {
+                    // A source that's lost one grain will keep losing every
+                    // grain after it the same way, so stop pouring from it.
+                    self.paths[i] = None;
+                    return Some(Outcome::Lost);
+                }
+                Some(next) => path.push(next),
+                None => {
+                    path.pop();
+                    self.grid.insert(sand, Tile::Sand);
+
+                    // Other sources' grains can't follow their old paths
+                    // through the new grain.
+                    for other in self.paths.iter_mut().flatten() {
+                        if let Some(j) = other.iter().position(|c| *c == sand) {
+                            other.truncate(j);
+                        }
+                    }
+
+                    return Some(Outcome::Settled(sand));
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Tile {
     Wall,
     Sand,
 }
 
-#[derive(Debug, Clone, Default)]
+/// A dense grid of tiles covering a fixed area of the cave, with an optional
+/// floor that stretches infinitely in both directions.
+#[derive(Debug, Clone)]
 struct Grid {
-    coords: HashMap<Coord<i32>, Tile>,
+    min: Coord<i32>,
+    width: usize,
+    tiles: Vec<Option<Tile>>,
+    floor_y: Option<i32>,
+    /// The smallest x, largest x and largest y of any tile placed so far.
+    bounds: Option<(i32, i32, i32)>,
 }
 
 impl Grid {
-    fn read_wall_lines(&mut self, input: &str) {
-        for line in input.lines() {
-            let mut points = line
-                .split(" -> ")
-                .map(|s| Coord::<i32>::from_str(s).unwrap());
-            let mut point = points.next().unwrap();
-            self.coords.insert(point, Tile::Wall);
-
-            for next_point in points {
-                let delta = next_point.unit_difference(point);
-                while point != next_point {
-                    point = point + delta;
-                    self.coords.insert(point, Tile::Wall);
-                }
-            }
+    fn new(min: Coord<i32>, max: Coord<i32>, floor_y: Option<i32>) -> Self {
+        let width = (max.0 - min.0 + 1) as usize;
+        let height = (max.1 - min.1 + 1) as usize;
+
+        Grid {
+            min,
+            width,
+            tiles: vec![None; width * height],
+            floor_y,
+            bounds: None,
         }
     }
 
-    fn min_x(&self) -> Option<i32> {
-        self.coords.keys().map(|Coord(x, _)| *x).min()
+    fn index(&self, coord: Coord<i32>) -> Option<usize> {
+        let Coord(x, y) = coord - self.min;
+        if x < 0 || y < 0 || x as usize >= self.width {
+            return None;
+        }
+
+        let i = y as usize * self.width + x as usize;
+        (i < self.tiles.len()).then_some(i)
     }
 
-    fn max_x(&self) -> Option<i32> {
-        self.coords.keys().map(|Coord(x, _)| *x).max()
+    fn coord(&self, index: usize) -> Coord<i32> {
+        self.min + Coord((index % self.width) as i32, (index / self.width) as i32)
+    }
+
+    fn get(&self, coord: Coord<i32>) -> Option<Tile> {
+        self.index(coord).and_then(|i| self.tiles[i])
+    }
+
+    fn insert(&mut self, coord: Coord<i32>, tile: Tile) {
+        let i = self
+            .index(coord)
+            .unwrap_or_else(|| panic!("{coord:?} is outside the grid"));
+        self.tiles[i] = Some(tile);
+
+        let Coord(x, y) = coord;
+        self.bounds = Some(match self.bounds {
+            Some((min_x, max_x, max_y)) => (min_x.min(x), max_x.max(x), max_y.max(y)),
+            None => (x, x, y),
+        });
     }
 
-    #[allow(unused)]
-    fn min_y(&self) -> Option<i32> {
-        self.coords.keys().map(|Coord(_, y)| *y).min()
+    fn is_blocked(&self, coord: Coord<i32>) -> bool {
+        self.floor_y.is_some_and(|floor_y| coord.1 >= floor_y) || self.get(coord).is_some()
+    }
+
+    fn min_x(&self) -> Option<i32> {
+        self.bounds.map(|(min_x, _, _)| min_x)
+    }
+
+    fn max_x(&self) -> Option<i32> {
+        self.bounds.map(|(_, max_x, _)| max_x)
     }
 
     fn max_y(&self) -> Option<i32> {
-        self.coords.keys().map(|Coord(_, y)| *y).max()
+        self.bounds.map(|(_, _, max_y)| max_y)
     }
 }
 
 impl Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let max_y = self.max_y().unwrap_or(0);
+        let max_y = self.floor_y.or(self.max_y()).unwrap_or(0);
         let min_x = self.min_x().unwrap_or(0);
         let max_x = self.max_x().unwrap_or(0);
 
         for y in 0..=max_y {
             for x in min_x..=max_x {
-                match self.coords.get(&Coord(x, y)) {
+                match self.get(Coord(x, y)) {
                     Some(x) => write!(f, "{}", x)?,
+                    None if self.floor_y == Some(y) => write!(f, "{}", Tile::Wall)?,
                     None => write!(f, ".")?,
                 }
             }
@@ -125,6 +440,41 @@ impl Display for Grid {
     }
 }
 
+impl TileMap for Grid {
+    fn bounds(&self) -> (Range, Range) {
+        let max_y = self.floor_y.or(self.max_y()).unwrap_or(0);
+        let min_x = self.min_x().unwrap_or(0);
+        let max_x = self.max_x().unwrap_or(0);
+
+        (Range(min_x as i64, max_x as i64), Range(0, max_y as i64))
+    }
+
+    fn color_at(&self, x: i64, y: i64) -> Rgb {
+        let (x, y) = (x as i32, y as i32);
+        match self.get(Coord(x, y)) {
+            Some(tile) => tile.color(),
+            None if self.floor_y == Some(y) => Tile::Wall.color(),
+            None => Rgb::BLACK,
+        }
+    }
+}
+
+impl Raster for Grid {
+    fn raster(&self) -> Canvas {
+        let (x, y) = self.bounds();
+        self.raster_within(x, y)
+    }
+}
+
+impl Palette for Tile {
+    fn color(&self) -> Rgb {
+        match self {
+            Tile::Wall => Rgb::GREY,
+            Tile::Sand => Rgb::GOLD,
+        }
+    }
+}
+
 impl Display for Tile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -137,3 +487,59 @@ impl Display for Tile {
         )
     }
 }
+
+#[test]
+fn simulate_works() {
+    let input = "\
+498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    assert_eq!(main(input), (24, 93));
+
+    let params = Params {
+        floor: false,
+        ..Params::default()
+    };
+    assert_eq!(main_with_params(input, &params), (24, 24));
+
+    // Whichever way grains prefer to slide, with a floor they end up filling
+    // every square the source can reach.
+    let params = Params {
+        fall_rule: FallRule::from_str("0,1 1,1 -1,1").unwrap(),
+        ..Params::default()
+    };
+    assert_eq!(main_with_params(input, &params).1, 93);
+
+    let params =
+        Params::with_overrides(&[("sources".to_string(), "500,0 500,0".to_string())]).unwrap();
+    assert_eq!(main_with_params(input, &params), (24, 93));
+
+    assert!(FallRule::from_str("0,1 1,0").is_err());
+    assert!(Params::with_overrides(&[("floor_distance".to_string(), "0".to_string())]).is_err());
+}
+
+#[test]
+fn fill_works() {
+    let input = "\
+498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    for (sources, fall_rule) in [
+        ("500,0", "0,1 -1,1 1,1"),
+        ("500,0 497,2", "0,1 -1,1 1,1"),
+        ("500,0", "0,1 2,1 -1,2"),
+        ("490,-3 500,0 510,0", "0,2 -1,1 1,1"),
+    ] {
+        let params = Params::with_overrides(&[
+            ("sources".to_string(), sources.to_string()),
+            ("fall_rule".to_string(), fall_rule.to_string()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            main_with_params(input, &params),
+            simulate(input, &params, |_| true),
+            "{sources} with {fall_rule}"
+        );
+    }
+}
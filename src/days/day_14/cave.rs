@@ -0,0 +1,241 @@
+//! Reading and writing cave scans, both in the puzzle's `x,y -> x,y` format
+//! and as the character art [`Grid`]'s `Display` draws.
+
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
+
+use crate::coord::Coord;
+
+use super::{Grid, Tile};
+
+/// A single path of rock from a scan, like `498,4 -> 498,6 -> 496,6`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RockPath {
+    points: Vec<Coord<i32>>,
+}
+
+impl RockPath {
+    /// Every square of rock along the path.
+    pub fn tiles(&self) -> impl Iterator<Item = Coord<i32>> + '_ {
+        self.points
+            .first()
+            .copied()
+            .into_iter()
+            .chain(self.points.windows(2).flat_map(|segment| {
+                let (from, to) = (segment[0], segment[1]);
+                let delta = to.unit_difference(from);
+                (1..=from.manhattan_distance(to))
+                    .map(move |i| from + Coord(delta.0 * i, delta.1 * i))
+            }))
+    }
+}
+
+impl FromStr for RockPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points: Vec<Coord<i32>> = s
+            .split("->")
+            .map(|point| {
+                Coord::from_str(point.trim()).map_err(|_| format!("invalid point {}", point.trim()))
+            })
+            .collect::<Result<_, _>>()?;
+
+        for segment in points.windows(2) {
+            let (a, b) = (segment[0], segment[1]);
+            if a.0 != b.0 && a.1 != b.1 {
+                return Err(format!(
+                    "diagonal segment {},{} -> {},{}",
+                    a.0, a.1, b.0, b.1
+                ));
+            }
+        }
+
+        Ok(RockPath { points })
+    }
+}
+
+impl Display for RockPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, Coord(x, y)) in self.points.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{x},{y}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses a scan, checking that every path is made of horizontal and
+/// vertical segments only.
+pub fn parse_scan(input: &str) -> Result<Vec<RockPath>, ScanError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            RockPath::from_str(line).map_err(|message| ScanError {
+                line: i + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Draws a scan the same way the simulation does.
+pub fn scan_to_art(input: &str) -> Result<String, ScanError> {
+    Ok(Grid::from_scan(&parse_scan(input)?).to_string())
+}
+
+/// Reads back character art like [`scan_to_art`] draws, whose leftmost
+/// column is at `min_x`, and writes out the rock in it as a scan.
+pub fn art_to_scan(art: &str, min_x: i32) -> Result<String, String> {
+    Ok(Grid::from_art(art, min_x)?.to_scan())
+}
+
+impl Grid {
+    fn from_scan(paths: &[RockPath]) -> Grid {
+        let tiles: Vec<Coord<i32>> = paths.iter().flat_map(RockPath::tiles).collect();
+        let min_x = tiles.iter().map(|Coord(x, _)| *x).min().unwrap_or(0);
+        let max_x = tiles.iter().map(|Coord(x, _)| *x).max().unwrap_or(0);
+        let max_y = tiles.iter().map(|Coord(_, y)| *y).max().unwrap_or(0);
+
+        let mut grid = Grid::new(Coord(min_x, 0), Coord(max_x, max_y), None);
+        for tile in tiles {
+            grid.insert(tile, Tile::Wall);
+        }
+
+        grid
+    }
+
+    /// Reads character art with `#` for rock, `o` for sand and `.` for air,
+    /// with the top row at y = 0.
+    fn from_art(art: &str, min_x: i32) -> Result<Grid, String> {
+        let rows: Vec<&str> = art.lines().filter(|line| !line.trim().is_empty()).collect();
+        let width = rows
+            .iter()
+            .map(|row| row.trim().len())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let height = rows.len().max(1);
+
+        let mut grid = Grid::new(
+            Coord(min_x, 0),
+            Coord(min_x + width as i32 - 1, height as i32 - 1),
+            None,
+        );
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.trim().chars().enumerate() {
+                let coord = Coord(min_x + x as i32, y as i32);
+                match c {
+                    '#' => grid.insert(coord, Tile::Wall),
+                    'o' => grid.insert(coord, Tile::Sand),
+                    '.' => {}
+                    c => return Err(format!("invalid tile {c} at {},{}", coord.0, coord.1)),
+                }
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Writes the grid's rock out as a scan, one path per horizontal or
+    /// vertical run of rock. Sand isn't part of a scan, so it's left out.
+    fn to_scan(&self) -> String {
+        let mut rock: BTreeSet<(i32, i32)> = (0..self.tiles.len())
+            .filter(|i| matches!(self.tiles[*i], Some(Tile::Wall)))
+            .map(|i| {
+                let Coord(x, y) = self.coord(i);
+                (y, x)
+            })
+            .collect();
+
+        let mut paths = Vec::new();
+
+        // Rows first, keeping single squares back in case they're part of a
+        // vertical run.
+        let mut singles = BTreeSet::new();
+        while let Some((y, x)) = rock.pop_first() {
+            let mut end = x;
+            while rock.remove(&(y, end + 1)) {
+                end += 1;
+            }
+
+            if end > x {
+                paths.push(RockPath {
+                    points: vec![Coord(x, y), Coord(end, y)],
+                });
+            } else {
+                singles.insert((x, y));
+            }
+        }
+
+        while let Some((x, y)) = singles.pop_first() {
+            let mut end = y;
+            while singles.remove(&(x, end + 1)) {
+                end += 1;
+            }
+
+            paths.push(RockPath {
+                points: vec![Coord(x, y), Coord(x, end)],
+            });
+        }
+
+        paths.iter().map(|path| format!("{path}\n")).collect()
+    }
+}
+
+#[test]
+fn scan_works() {
+    let input = "\
+498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    let paths = parse_scan(input).unwrap();
+    assert_eq!(paths[1].to_string(), "503,4 -> 502,4 -> 502,9 -> 494,9");
+    assert_eq!(paths[0].tiles().count(), 5);
+
+    let art = scan_to_art(input).unwrap();
+    assert_eq!(
+        art,
+        "\
+..........
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+........#.
+#########.
+"
+    );
+
+    let scan = art_to_scan(&art, 494).unwrap();
+    assert_eq!(scan_to_art(&scan).unwrap(), art);
+
+    assert_eq!(
+        parse_scan("498,4 -> 498,6\n498,6 -> 500,8"),
+        Err(ScanError {
+            line: 2,
+            message: "diagonal segment 498,6 -> 500,8".to_string()
+        })
+    );
+    assert!(parse_scan("498,4 -> x,6").is_err());
+    assert!(art_to_scan("..#\n.+.", 0).is_err());
+}
@@ -2,45 +2,141 @@ use std::{collections::HashSet, str::FromStr};
 
 use crate::{
     coord::Coord,
+    params::{parse_param, unknown_param, Parameters},
     range::{Coverage, Range},
 };
 
+const TUNING_FREQUENCY_MULTIPLIER: i64 = 4000000;
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub row: i64,
+    pub search_min: i64,
+    pub search_max: i64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            row: 2000000,
+            search_min: 0,
+            search_max: 4000000,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "row" => self.row = parse_param(key, value)?,
+            "search_min" => self.search_min = parse_param(key, value)?,
+            "search_max" => self.search_max = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.search_min > self.search_max {
+            return Err(format!(
+                "search_min ({}) is greater than search_max ({})",
+                self.search_min, self.search_max
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 pub fn main(input: &str) -> (u64, u64) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u64, u64) {
+    params.validate().unwrap_or_else(|e| panic!("{e}"));
+
     let sensors: Vec<Sensor> = input
         .lines()
         .map(|line| Sensor::from_str(line).unwrap())
         .collect();
 
-    let (coverage, beacons) = get_coverage(&sensors, 2000000);
+    let (coverage, beacons) = get_coverage(&sensors, params.row);
     let cannot_contain = coverage.area_covered() as u64 - beacons.len() as u64;
 
-    let can_contain = find_possible_position(&sensors, Range(0, 4000000)).unwrap();
-    let tuning_frequency = (can_contain.0 * 4000000 + can_contain.1) as u64;
+    let x = Range::new(params.search_min, params.search_max).unwrap();
+    if cfg!(feature = "debug") {
+        eprintln!("{}", render(&sensors, x, x, (x.width() / 80).max(1)));
+    }
+
+    let can_contain = *uncovered_cells(&sensors, x, x).first().unwrap();
+    let tuning_frequency = (can_contain.0 * TUNING_FREQUENCY_MULTIPLIER + can_contain.1) as u64;
 
     (cannot_contain, tuning_frequency)
 }
 
-fn find_possible_position(sensors: &Vec<Sensor>, search_range: Range) -> Option<Coord<i64>> {
-    for y in search_range.0..=search_range.1 {
-        let (coverage, _) = get_coverage(&sensors, y);
+/// Finds every cell in the rectangle spanning `x` and `y` that no sensor
+/// covers, ordered by row and then column.
+///
+/// In rotated coordinates, u = x + y and v = x - y, each sensor's diamond is
+/// an axis-aligned square. Splitting the plane at the squares' edges leaves a
+/// grid of blocks that are each either wholly inside a square or wholly
+/// outside all of them, so only the uncovered blocks need their cells listing.
+pub fn uncovered_cells(sensors: &[Sensor], x: Range, y: Range) -> Vec<Coord<i64>> {
+    let squares: Vec<(Range, Range)> = sensors.iter().map(Sensor::rotated).collect();
+    let u_blocks = blocks(Range(x.0 + y.0, x.1 + y.1), squares.iter().map(|(u, _)| *u));
+    let v_blocks = blocks(Range(x.0 - y.1, x.1 - y.0), squares.iter().map(|(_, v)| *v));
 
-        if let Some(bounds) = coverage.bounds() {
-            if search_range.0 < bounds.0 {
-                return Some(Coord(search_range.0, y));
-            } else if search_range.1 > bounds.1 {
-                return Some(Coord(search_range.1, y));
+    let mut cells = Vec::new();
+    for u in &u_blocks {
+        for v in &v_blocks {
+            let covered = squares.iter().any(|(square_u, square_v)| {
+                square_u.intersection(*u).is_some() && square_v.intersection(*v).is_some()
+            });
+            if !covered {
+                cells.extend(cells_in_block(*u, *v, x, y));
             }
-        } else {
-            return Some(Coord(search_range.0, y));
-        }
-
-        let gaps = coverage.gaps();
-        if let Some(range) = gaps.ranges().first() {
-            return Some(Coord(range.0, y));
         }
     }
 
-    None
+    cells.sort_by_key(|Coord(x, y)| (*y, *x));
+    cells
+}
+
+/// Splits `bounds` wherever one of `ranges` starts or ends.
+fn blocks(bounds: Range, ranges: impl Iterator<Item = Range>) -> Vec<Range> {
+    let mut edges: Vec<i64> = ranges
+        .flat_map(|range| [range.0, range.1 + 1])
+        .filter(|edge| bounds.0 < *edge && *edge <= bounds.1)
+        .chain([bounds.0, bounds.1 + 1])
+        .collect();
+    edges.sort();
+    edges.dedup();
+
+    edges
+        .windows(2)
+        .map(|pair| Range(pair[0], pair[1] - 1))
+        .collect()
+}
+
+/// The cells in the rotated block spanning `u` and `v` that are also inside
+/// the rectangle spanning `x` and `y`.
+fn cells_in_block(u: Range, v: Range, x: Range, y: Range) -> impl Iterator<Item = Coord<i64>> {
+    // With x = (u + v) / 2 and y = (u - v) / 2, the rectangle limits v to a
+    // range for each u, which is only non-empty for some of the block's u.
+    let u_min = u.0.max(v.0 + 2 * y.0).max(2 * x.0 - v.1).max(x.0 + y.0);
+    let u_max = u.1.min(v.1 + 2 * y.1).min(2 * x.1 - v.0).min(x.1 + y.1);
+
+    (u_min..=u_max).flat_map(move |u| {
+        let v_min = v.0.max(2 * x.0 - u).max(u - 2 * y.1);
+        let v_max = v.1.min(2 * x.1 - u).min(u - 2 * y.0);
+
+        // Only points where u and v are both odd or both even are cells.
+        let v_min = v_min + (v_min - u).rem_euclid(2);
+        (v_min..=v_max)
+            .step_by(2)
+            .map(move |v| Coord((u + v) / 2, (u - v) / 2))
+    })
 }
 
 #[test]
@@ -70,54 +166,158 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3
     let cannot_contain = coverage.area_covered() as u64 - beacons.len() as u64;
     assert_eq!(cannot_contain, 26);
 
-    let can_contain = find_possible_position(&sensors, Range(0, 20)).unwrap();
-    dbg!(can_contain);
+    let can_contain = uncovered_cells(&sensors, Range(0, 20), Range(0, 20));
+    assert_eq!(can_contain, vec![Coord(14, 11)]);
+    let can_contain = can_contain[0];
 
-    let tuning_frequency = (can_contain.0 * 4000000 + can_contain.1) as u64;
+    let tuning_frequency = (can_contain.0 * TUNING_FREQUENCY_MULTIPLIER + can_contain.1) as u64;
 
     assert_eq!(tuning_frequency, 56000011);
+
+    let overrides = [
+        ("search_min".to_string(), "10".to_string()),
+        ("search_max".to_string(), "0".to_string()),
+    ];
+    assert!(Params::with_overrides(&overrides).is_err());
+    assert!(Params::with_overrides(&overrides[..1]).is_ok());
+
+    let (x, y) = (Range(-10, 35), Range(-5, 30));
+    let expected: Vec<Coord<i64>> = y
+        .into_iter()
+        .flat_map(|y| x.into_iter().map(move |x| Coord(x, y)))
+        .filter(|cell| {
+            !sensors
+                .iter()
+                .any(|sensor| sensor.within_beacon_range(*cell))
+        })
+        .collect();
+    assert!(expected.len() > 1);
+    assert_eq!(uncovered_cells(&sensors, x, y), expected);
+}
+
+fn get_coverage(sensors: &[Sensor], y: i64) -> (Coverage, HashSet<Coord<i64>>) {
+    let beacons = sensors
+        .iter()
+        .map(|sensor| sensor.closest_beacon)
+        .filter(|beacon| beacon.1 == y)
+        .collect();
+
+    (row_coverage(sensors, y), beacons)
 }
 
-fn get_coverage(sensors: &Vec<Sensor>, y: i64) -> (Coverage, HashSet<Coord<i64>>) {
-    let mut beacons: HashSet<Coord<i64>> = HashSet::new();
-    let coverage = Coverage::new(
+/// The cells in row `y` that are covered by at least one sensor.
+pub fn row_coverage(sensors: &[Sensor], y: i64) -> Coverage {
+    Coverage::new(
         sensors
             .iter()
-            .filter_map(|sensor| {
-                let md = sensor.beacon_distance();
-                let width_delta = md - y.abs_diff(sensor.pos.1) as i64;
-                if width_delta <= 0 {
-                    None
-                } else {
-                    if sensor.closest_beacon.1 == y {
-                        beacons.insert(sensor.closest_beacon);
-                    }
-                    Some(
-                        Range::new(sensor.pos.0 - width_delta, sensor.pos.0 + width_delta).unwrap(),
-                    )
-                }
-            })
+            .filter_map(|sensor| sensor.row_range(y))
             .collect(),
-    );
+    )
+}
 
-    (coverage, beacons)
+/// The cells in column `x` that are covered by at least one sensor.
+pub fn column_coverage(sensors: &[Sensor], x: i64) -> Coverage {
+    Coverage::new(
+        sensors
+            .iter()
+            .filter_map(|sensor| sensor.column_range(x))
+            .collect(),
+    )
+}
+
+/// The sensors whose coverage includes `point`.
+pub fn sensors_covering(sensors: &[Sensor], point: Coord<i64>) -> Vec<&Sensor> {
+    sensors
+        .iter()
+        .filter(|sensor| sensor.within_beacon_range(point))
+        .collect()
+}
+
+/// How many cells in the rectangle spanning `x` and `y` are covered by at
+/// least one sensor.
+pub fn covered_area(sensors: &[Sensor], x: Range, y: Range) -> i64 {
+    y.into_iter()
+        .map(|y| row_coverage(sensors, y).clipped(x).area_covered())
+        .sum()
+}
+
+/// Draws the sensors' coverage of the rectangle spanning `x` and `y`, with
+/// each character standing for a `scale` by `scale` square of cells.
+///
+/// Squares holding a sensor or a beacon are drawn as `S` or `B`. Otherwise
+/// they're `#` if every cell in them is covered, `.` if none are, and `+` if
+/// only some are.
+pub fn render(sensors: &[Sensor], x: Range, y: Range, scale: i64) -> String {
+    let scale = scale.max(1);
+    let mut out = String::new();
+
+    for top in y.into_iter().step_by(scale as usize) {
+        let rows = Range(top, (top + scale - 1).min(y.1));
+        let coverages: Vec<Coverage> = rows.into_iter().map(|y| row_coverage(sensors, y)).collect();
+
+        for left in x.into_iter().step_by(scale as usize) {
+            let columns = Range(left, (left + scale - 1).min(x.1));
+            let inside = |Coord(x, y): Coord<i64>| {
+                columns.0 <= x && x <= columns.1 && rows.0 <= y && y <= rows.1
+            };
+            let covered: i64 = coverages
+                .iter()
+                .map(|coverage| coverage.clipped(columns).area_covered())
+                .sum();
+
+            out.push(if sensors.iter().any(|sensor| inside(sensor.pos)) {
+                'S'
+            } else if sensors.iter().any(|sensor| inside(sensor.closest_beacon)) {
+                'B'
+            } else if covered == columns.width() * rows.width() {
+                '#'
+            } else if covered == 0 {
+                '.'
+            } else {
+                '+'
+            });
+        }
+        out.push('\n');
+    }
+
+    out
 }
 
 #[derive(Debug)]
-struct Sensor {
-    pos: Coord<i64>,
-    closest_beacon: Coord<i64>,
+pub struct Sensor {
+    pub pos: Coord<i64>,
+    pub closest_beacon: Coord<i64>,
 }
 
 impl Sensor {
-    fn beacon_distance(&self) -> i64 {
+    pub fn beacon_distance(&self) -> i64 {
         self.pos.manhattan_distance(self.closest_beacon)
     }
 
-    #[allow(unused)]
-    fn within_beacon_range(&self, target: Coord<i64>) -> bool {
+    pub fn within_beacon_range(&self, target: Coord<i64>) -> bool {
         self.pos.manhattan_distance(target) <= self.beacon_distance()
     }
+
+    /// The cells the sensor covers in row `y`, if any.
+    pub fn row_range(&self, y: i64) -> Option<Range> {
+        let half_width = self.beacon_distance() - (y - self.pos.1).abs();
+        Range::new(self.pos.0 - half_width, self.pos.0 + half_width).ok()
+    }
+
+    /// The cells the sensor covers in column `x`, if any.
+    pub fn column_range(&self, x: i64) -> Option<Range> {
+        let half_height = self.beacon_distance() - (x - self.pos.0).abs();
+        Range::new(self.pos.1 - half_height, self.pos.1 + half_height).ok()
+    }
+
+    /// The square the sensor covers in coordinates rotated by 45 degrees,
+    /// u = x + y and v = x - y.
+    fn rotated(&self) -> (Range, Range) {
+        let r = self.beacon_distance();
+        let Coord(x, y) = self.pos;
+
+        (Range(x + y - r, x + y + r), Range(x - y - r, x - y + r))
+    }
 }
 
 impl FromStr for Sensor {
@@ -178,3 +378,61 @@ impl FromStr for Sensor {
         })
     }
 }
+
+#[test]
+fn queries_work() {
+    let sensors: Vec<Sensor> = "
+Sensor at x=8, y=7: closest beacon is at x=2, y=10
+Sensor at x=20, y=1: closest beacon is at x=15, y=3"
+        .trim()
+        .lines()
+        .map(|line| Sensor::from_str(line).unwrap())
+        .collect();
+
+    let (x, y) = (Range(-2, 27), Range(-3, 17));
+    assert_eq!(
+        render(&sensors, x, y, 3),
+        "\
+...+.++#++
+..+#++#S#+
+.+###B+#++
++##S##+++.
+.B###+....
+..+#+.....
+...+......
+"
+    );
+
+    let brute_force = |cells: &mut dyn Iterator<Item = Coord<i64>>| {
+        cells
+            .filter(|cell| !sensors_covering(&sensors, *cell).is_empty())
+            .count() as i64
+    };
+    assert_eq!(
+        row_coverage(&sensors, 7),
+        Coverage::new(vec![Range(-1, 17), Range(19, 21)])
+    );
+    assert_eq!(
+        row_coverage(&sensors, 2).area_covered(),
+        brute_force(&mut x.into_iter().map(|x| Coord(x, 2)))
+    );
+    assert_eq!(
+        column_coverage(&sensors, 16).area_covered(),
+        brute_force(&mut y.into_iter().map(|y| Coord(16, y)))
+    );
+    assert_eq!(
+        covered_area(&sensors, x, y),
+        brute_force(
+            &mut y
+                .into_iter()
+                .flat_map(|y| x.into_iter().map(move |x| Coord(x, y)))
+        )
+    );
+
+    assert_eq!(
+        sensors_covering(&sensors, Coord(15, 3))[0].pos,
+        Coord(20, 1)
+    );
+    assert_eq!(sensors_covering(&sensors, Coord(8, 16))[0].pos, Coord(8, 7));
+    assert!(sensors_covering(&sensors, Coord(0, 0)).is_empty());
+}
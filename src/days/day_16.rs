@@ -1,398 +1,895 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Mutex};
 
 #[cfg(feature = "multiprocessing")]
-use std::sync::{Arc, RwLock};
+use dashmap::DashMap;
 
 #[cfg(feature = "multiprocessing")]
 use rayon::prelude::*;
 
-pub fn main(input: &str) -> (u32, u32) {
-    let mut flows: HashMap<&str, u32> = HashMap::new();
-    let mut tunnels: HashMap<&str, Vec<String>> = HashMap::new();
-
-    for line in input.lines() {
-        let mut chunks = line.trim().split("; ");
-        let mut words = chunks.next().unwrap().split_whitespace().skip(1);
-        let valve = words.next().unwrap();
-        flows.insert(
-            valve,
-            words
-                .skip(2)
-                .next()
-                .unwrap()
-                .split('=')
-                .skip(1)
-                .next()
-                .unwrap()
-                .replace(';', "")
-                .parse()
-                .unwrap(),
-        );
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub start: String,
+    pub alone_minutes: u32,
+    pub elephant_minutes: u32,
+    /// Who works together in part 2. Without this, it's you and the elephant,
+    /// both starting at `start` with `elephant_minutes` to work with.
+    pub agents: Option<Vec<Agent>>,
+    /// Searches on every thread available, rather than just one.
+    pub parallel: bool,
+}
 
-        let words = chunks.next().unwrap().split_whitespace().skip(4);
-        tunnels.insert(valve, words.map(|word| word.replace(',', "")).collect());
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            start: "AA".to_string(),
+            alone_minutes: 30,
+            elephant_minutes: 26,
+            agents: None,
+            parallel: cfg!(feature = "multiprocessing"),
+        }
     }
+}
 
-    let alone_max = max_pressure(
-        &flows,
-        &tunnels,
-        30,
-        ValvePosition {
-            valve: "AA",
-            open_time_end: 0,
-        },
-        None,
-    );
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "start" => self.start = value.to_string(),
+            "alone_minutes" => self.alone_minutes = parse_param(key, value)?,
+            "elephant_minutes" => self.elephant_minutes = parse_param(key, value)?,
+            "agents" => {
+                self.agents = Some(
+                    value
+                        .split_whitespace()
+                        .map(|s| parse_param(key, s))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            "parallel" => {
+                let parallel = parse_param(key, value)?;
+                if parallel && cfg!(not(feature = "multiprocessing")) {
+                    return Err("parallel requires the `multiprocessing` feature".to_string());
+                }
+                self.parallel = parallel;
+            }
+            key => return unknown_param(key),
+        }
 
-    let with_elephant_max = max_pressure(
-        &flows,
-        &tunnels,
-        26,
-        ValvePosition {
-            valve: "AA",
-            open_time_end: 0,
-        },
-        Some(ValvePosition {
-            valve: "AA",
-            open_time_end: 0,
-        }),
-    );
+        Ok(())
+    }
 
-    (alone_max, with_elephant_max)
+    fn validate(&self) -> Result<(), String> {
+        if self.agents.as_ref().is_some_and(Vec::is_empty) {
+            return Err("agents needs at least one agent".to_string());
+        }
+
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct ValvePosition<'input> {
-    valve: &'input str,
-    open_time_end: u32,
+impl Params {
+    fn alone(&self) -> Vec<Agent> {
+        vec![Agent {
+            start: self.start.clone(),
+            minutes: self.alone_minutes,
+        }]
+    }
+
+    fn agents(&self) -> Vec<Agent> {
+        self.agents.clone().unwrap_or_else(|| {
+            let agent = Agent {
+                start: self.start.clone(),
+                minutes: self.elephant_minutes,
+            };
+            vec![agent.clone(), agent]
+        })
+    }
+}
+
+/// Someone opening valves, and how long they have to do it in.
+///
+/// Everyone finishes at the same time, so someone with fewer minutes than the
+/// others starts later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agent {
+    pub start: String,
+    pub minutes: u32,
+}
+
+impl FromStr for Agent {
+    type Err = String;
+
+    /// Parses an agent written as `start:minutes`, like `AA:26`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, minutes) = s
+            .split_once(':')
+            .ok_or(format!("agent missing `:` separator: {s}"))?;
+
+        Ok(Agent {
+            start: start.to_string(),
+            minutes: minutes
+                .parse()
+                .map_err(|e| format!("agent invalid minutes: {s} ({e})"))?,
+        })
+    }
+}
+
+pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
+    let (alone, with_elephant) = plans(input, params);
+
+    (alone.released(), with_elephant.released())
+}
+
+/// Describes the best plans, working alone and together, minute by minute.
+pub fn explain(input: &str, params: &Params) -> String {
+    let (alone, with_elephant) = plans(input, params);
+
+    format!(
+        "Working alone releases {} pressure:\n\n{alone}Working together releases {} pressure:\n\n{with_elephant}",
+        alone.released(),
+        with_elephant.released(),
+    )
+}
+
+/// Finds the plans that release the most pressure working alone and
+/// together.
+pub fn plans(input: &str, params: &Params) -> (Plan, Plan) {
+    let graph = Graph::from_str(input).unwrap();
+
+    #[cfg(feature = "multiprocessing")]
+    if params.parallel {
+        return (
+            plan(&Parallel, &graph, &params.alone()),
+            plan(&Parallel, &graph, &params.agents()),
+        );
+    }
+
+    (
+        plan(&Sequential, &graph, &params.alone()),
+        plan(&Sequential, &graph, &params.agents()),
+    )
 }
 
-fn get_adjacency_matrix<'input>(
-    flows: &'input HashMap<&'input str, u32>,
-    tunnels: &'input HashMap<&'input str, Vec<String>>,
-) -> HashMap<&'input str, HashMap<&'input str, u32>> {
-    let mut adjacency_matrix: HashMap<&'input str, HashMap<&'input str, u32>> = HashMap::new();
-    let valves_with_flows: Vec<&'input str> = flows
+/// Draws the valves in Graphviz's DOT language, leaving out the valves along
+/// tunnels that there's no point stopping at.
+pub fn dot(input: &str, params: &Params) -> String {
+    let graph = Graph::from_str(input).unwrap();
+    let starts: Vec<usize> = params
+        .alone()
         .iter()
-        .filter_map(|(valve, flow)| if *flow > 0 { Some(*valve) } else { None })
+        .chain(&params.agents())
+        .map(|agent| graph.index(&agent.start))
         .collect();
 
-    for source in tunnels.keys().copied() {
-        let mut dist: HashMap<&'input str, u32> = HashMap::new();
-        let mut prev: HashMap<&'input str, &'input str> = HashMap::new();
-        let mut queue: HashSet<&'input str> = HashSet::new();
+    graph.to_dot(&starts)
+}
+
+/// A set of the valves worth opening, with a bit for each.
+type ValveSet = u64;
+
+/// The valves and the tunnels between them, numbered so that the valves worth
+/// opening come first.
+#[derive(Debug, Clone)]
+struct Graph {
+    names: Vec<String>,
+    flows: Vec<u32>,
+    tunnels: Vec<Vec<usize>>,
+    /// How many valves have a flow rate above zero.
+    useful: usize,
+    /// The number of minutes it takes to walk between each pair of valves.
+    distances: Vec<Vec<u32>>,
+    /// The next valve on a shortest route between each pair of valves.
+    next: Vec<Vec<usize>>,
+}
 
-        for valve in tunnels.keys().copied() {
-            dist.insert(valve, u32::MAX);
-            queue.insert(valve);
+impl FromStr for Graph {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut valves: Vec<(&str, u32, Vec<&str>)> = s
+            .lines()
+            .map(|line| {
+                let (valve, tunnels) = line
+                    .trim()
+                    .split_once("; ")
+                    .ok_or(format!("input missing `;` separator: {line}"))?;
+                let name = valve
+                    .split_whitespace()
+                    .nth(1)
+                    .ok_or(format!("valve missing name: {line}"))?;
+                let flow = valve
+                    .split('=')
+                    .nth(1)
+                    .ok_or(format!("valve missing flow rate: {line}"))?
+                    .parse()
+                    .map_err(|e| format!("valve invalid flow rate: {line} ({e})"))?;
+                let tunnels = tunnels
+                    .split_whitespace()
+                    .skip(4)
+                    .map(|word| word.trim_end_matches(','))
+                    .collect();
+
+                Ok((name, flow, tunnels))
+            })
+            .collect::<Result<_, String>>()?;
+
+        valves.sort_by_key(|(_, flow, _)| *flow == 0);
+        let useful = valves.iter().filter(|(_, flow, _)| *flow > 0).count();
+        if useful > ValveSet::BITS as usize {
+            return Err(format!("too many valves with a flow rate: {useful}"));
         }
-        dist.insert(source, 0);
-
-        while !queue.is_empty() {
-            let (valve, valve_dist) = queue.iter().copied().fold(("", u32::MAX), |acc, valve| {
-                let valve_dist = *dist.get(valve).unwrap();
-                if valve_dist < acc.1 {
-                    (valve, valve_dist)
-                } else {
-                    acc
+
+        let names: Vec<String> = valves.iter().map(|(name, _, _)| name.to_string()).collect();
+        let index = |name: &str| {
+            names
+                .iter()
+                .position(|other| other == name)
+                .ok_or(format!("tunnel to unknown valve {name}"))
+        };
+        let tunnels: Vec<Vec<usize>> = valves
+            .iter()
+            .map(|(_, _, tunnels)| tunnels.iter().map(|name| index(name)).collect())
+            .collect::<Result<_, _>>()?;
+
+        // Floyd-Warshall, keeping track of where to go next to follow each
+        // shortest route.
+        let n = names.len();
+        let mut distances = vec![vec![u32::MAX; n]; n];
+        let mut next = vec![vec![usize::MAX; n]; n];
+        for (from, tunnels) in tunnels.iter().enumerate() {
+            distances[from][from] = 0;
+            next[from][from] = from;
+            for to in tunnels {
+                distances[from][*to] = 1;
+                next[from][*to] = *to;
+            }
+        }
+        for via in 0..n {
+            for from in 0..n {
+                for to in 0..n {
+                    let distance = distances[from][via].saturating_add(distances[via][to]);
+                    if distance < distances[from][to] {
+                        distances[from][to] = distance;
+                        next[from][to] = next[from][via];
+                    }
                 }
-            });
+            }
+        }
+
+        Ok(Graph {
+            names,
+            flows: valves.iter().map(|(_, flow, _)| *flow).collect(),
+            tunnels,
+            useful,
+            distances,
+            next,
+        })
+    }
+}
 
-            queue.remove(valve);
+impl Graph {
+    fn index(&self, name: &str) -> usize {
+        self.names
+            .iter()
+            .position(|other| other == name)
+            .unwrap_or_else(|| panic!("no valve named {name}"))
+    }
 
-            for neighbor in tunnels.get(valve).unwrap() {
-                let neighbor = neighbor.as_str();
-                if !queue.contains(neighbor) {
-                    continue;
+    /// When a valve would be open by, walking there from `from` and opening
+    /// it, if that's before time runs out.
+    fn open_time(&self, from: ValvePosition, to: usize, total_time: u32) -> Option<u32> {
+        let distance = self.distances[from.valve][to];
+        (distance != u32::MAX)
+            .then(|| from.open_time_end + distance + 1)
+            .filter(|open_time_end| *open_time_end < total_time)
+    }
+
+    /// The valves passed through on a shortest route between two valves,
+    /// ending with `to`.
+    fn route(&self, from: usize, to: usize) -> Vec<usize> {
+        let mut route = Vec::new();
+        let mut valve = from;
+        while valve != to {
+            valve = self.next[valve][to];
+            route.push(valve);
+        }
+
+        route
+    }
+
+    /// Draws the graph in the DOT language with `starts` marked out. Valves
+    /// with no flow that only lead on to one other valve are skipped over,
+    /// with each tunnel labelled with how long it takes to walk.
+    fn to_dot(&self, starts: &[usize]) -> String {
+        let kept = |valve: usize| {
+            self.flows[valve] > 0 || starts.contains(&valve) || self.tunnels[valve].len() != 2
+        };
+
+        let mut out = String::from("graph valves {\n");
+        for valve in (0..self.names.len()).filter(|valve| kept(*valve)) {
+            let name = &self.names[valve];
+            let shape = if starts.contains(&valve) {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            if self.flows[valve] > 0 {
+                out.push_str(&format!(
+                    "    {name} [label=\"{name}\\nflow {}\", shape={shape}];\n",
+                    self.flows[valve]
+                ));
+            } else {
+                out.push_str(&format!("    {name} [shape={shape}];\n"));
+            }
+        }
+
+        let mut edges: Vec<(usize, usize, u32)> = Vec::new();
+        for from in (0..self.names.len()).filter(|valve| kept(*valve)) {
+            for first in &self.tunnels[from] {
+                // Follow the tunnel through any valves that aren't kept.
+                let (mut prev, mut valve, mut length) = (from, *first, 1);
+                while !kept(valve) {
+                    let onward = self.tunnels[valve].iter().find(|next| **next != prev);
+                    (prev, valve) = (valve, *onward.unwrap_or(&prev));
+                    length += 1;
                 }
 
-                let alt = valve_dist + 1;
-                if alt < *dist.get(neighbor).unwrap() {
-                    dist.insert(neighbor, alt);
-                    prev.insert(neighbor, valve);
+                if from < valve && !edges.contains(&(from, valve, length)) {
+                    edges.push((from, valve, length));
                 }
             }
         }
+        for (from, to, length) in edges {
+            out.push_str(&format!(
+                "    {} -- {} [label=\"{length}\"];\n",
+                self.names[from], self.names[to]
+            ));
+        }
+        out.push_str("}\n");
 
-        for dest in valves_with_flows.iter().copied() {
-            let mut dist = 0;
-            let mut u = Some(dest);
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ValvePosition {
+    valve: usize,
+    open_time_end: u32,
+}
+
+/// Something an agent spends a minute doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Move(String),
+    Open(String),
+}
+
+/// What each agent does minute by minute to release as much pressure as
+/// possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// What each agent does each minute, or `None` for minutes they spend
+    /// waiting around, with you as agent 0.
+    actions: Vec<Vec<Option<Action>>>,
+    flows: HashMap<String, u32>,
+}
 
-            while let Some(valve) = u {
-                dist += 1;
-                u = prev.get(valve).copied();
+impl Plan {
+    fn new(graph: &Graph, agents: &[Agent], orders: &[Vec<ValvePosition>]) -> Self {
+        let total_time = agents.iter().map(|agent| agent.minutes).max().unwrap_or(0);
+        let mut actions = vec![vec![None; total_time as usize]; agents.len()];
+
+        for ((agent, order), actions) in agents.iter().zip(orders).zip(&mut actions) {
+            // Agents with less time to work with start later.
+            let late = (total_time - agent.minutes) as usize;
+            let mut from = ValvePosition {
+                valve: graph.index(&agent.start),
+                open_time_end: 0,
+            };
+
+            for to in order {
+                for (i, valve) in graph.route(from.valve, to.valve).into_iter().enumerate() {
+                    actions[late + from.open_time_end as usize + i] =
+                        Some(Action::Move(graph.names[valve].clone()));
+                }
+                actions[late + to.open_time_end as usize - 1] =
+                    Some(Action::Open(graph.names[to.valve].clone()));
+                from = *to;
             }
+        }
 
-            adjacency_matrix
-                .entry(source)
-                .or_default()
-                .insert(dest, dist);
+        Plan {
+            actions,
+            flows: orders
+                .iter()
+                .flatten()
+                .map(|to| (graph.names[to.valve].clone(), graph.flows[to.valve]))
+                .collect(),
         }
     }
 
-    adjacency_matrix
+    /// The valves that are open during `minute`, counting from 1, in
+    /// alphabetical order.
+    pub fn open_valves(&self, minute: u32) -> Vec<&str> {
+        let mut open: Vec<&str> = self
+            .actions
+            .iter()
+            .flat_map(|actions| &actions[..minute as usize - 1])
+            .filter_map(|action| match action {
+                Some(Action::Open(valve)) => Some(valve.as_str()),
+                _ => None,
+            })
+            .collect();
+        open.sort();
+
+        open
+    }
+
+    /// How much pressure is released in each minute.
+    pub fn released_per_minute(&self) -> Vec<u32> {
+        (1..=self.minutes())
+            .map(|minute| {
+                self.open_valves(minute)
+                    .iter()
+                    .map(|valve| self.flows.get(*valve).unwrap())
+                    .sum()
+            })
+            .collect()
+    }
+
+    pub fn released(&self) -> u32 {
+        self.released_per_minute().iter().sum()
+    }
+
+    pub fn minutes(&self) -> u32 {
+        self.actions
+            .first()
+            .map_or(0, |actions| actions.len() as u32)
+    }
 }
 
-type Cache<'input> = HashMap<
-    (
-        BTreeSet<&'input str>,
-        u32,
-        ValvePosition<'input>,
-        Option<ValvePosition<'input>>,
-    ),
-    u32,
->;
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let released = self.released_per_minute();
+
+        for minute in 1..=self.minutes() {
+            writeln!(f, "== Minute {minute} ==")?;
+
+            let open = self.open_valves(minute);
+            let pressure = released[minute as usize - 1];
+            match open.as_slice() {
+                [] => writeln!(f, "No valves are open.")?,
+                [valve] => writeln!(f, "Valve {valve} is open, releasing {pressure} pressure.")?,
+                [first, second] => writeln!(
+                    f,
+                    "Valves {first} and {second} are open, releasing {pressure} pressure."
+                )?,
+                [rest @ .., last] => writeln!(
+                    f,
+                    "Valves {}, and {last} are open, releasing {pressure} pressure.",
+                    rest.join(", ")
+                )?,
+            }
 
-#[cfg(feature = "multiprocessing")]
-fn max_pressure<'input>(
-    flows: &HashMap<&'input str, u32>,
-    tunnels: &'input HashMap<&'input str, Vec<String>>,
-    time_remaining: u32,
-    you: ValvePosition<'input>,
-    elephant: Option<ValvePosition<'input>>,
-) -> u32 {
-    let adjacency_matrix = get_adjacency_matrix(&flows, &tunnels);
-
-    let closed: BTreeSet<&str> = flows
+            for (actor, actions) in self.actions.iter().enumerate() {
+                let action = &actions[minute as usize - 1];
+                let name = match (actor, self.actions.len()) {
+                    (0, _) => "You".to_string(),
+                    (_, 2) => "The elephant".to_string(),
+                    (actor, _) => format!("Elephant {actor}"),
+                };
+                let s = if actor == 0 { "" } else { "s" };
+
+                match action {
+                    Some(Action::Move(valve)) => writeln!(f, "{name} move{s} to valve {valve}.")?,
+                    Some(Action::Open(valve)) => writeln!(f, "{name} open{s} valve {valve}.")?,
+                    None => {}
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the plan that releases the most pressure with `agents` working
+/// together.
+///
+/// No two agents ever open the same valve, so this works out the most each
+/// agent could release opening every possible set of valves by themselves,
+/// then picks the best way of sharing the valves out between them.
+fn plan<S: Strategy>(strategy: &S, graph: &Graph, agents: &[Agent]) -> Plan {
+    let starts: Vec<ValvePosition> = agents
+        .iter()
+        .map(|agent| ValvePosition {
+            valve: graph.index(&agent.start),
+            open_time_end: 0,
+        })
+        .collect();
+
+    let mut tables: Vec<Scores> = Vec::new();
+    for (i, agent) in agents.iter().enumerate() {
+        let table = match agents[..i].iter().position(|other| other == agent) {
+            Some(j) => tables[j].clone(),
+            None => max_pressure(strategy, graph, agent.minutes, starts[i]),
+        };
+        tables.push(table);
+    }
+
+    let orders: Vec<Vec<ValvePosition>> = agents
         .iter()
-        .filter_map(|(valve, flow)| if *flow > 0 { Some(*valve) } else { None })
+        .zip(starts)
+        .zip(best_split(&tables))
+        .map(|((agent, start), valves)| best_order(graph, valves, agent.minutes, start).1)
         .collect();
 
-    max_pressure_inner(
-        Arc::new(RwLock::new(HashMap::new())),
-        flows,
-        &adjacency_matrix,
-        closed,
-        time_remaining,
-        you,
-        elephant,
-    )
+    Plan::new(graph, agents, &orders)
 }
 
-#[cfg(feature = "multiprocessing")]
-fn max_pressure_inner<'input>(
-    cache: Arc<RwLock<Cache<'input>>>,
-    flows: &HashMap<&'input str, u32>,
-    adjacency_matrix: &'input HashMap<&'input str, HashMap<&'input str, u32>>,
-    closed: BTreeSet<&'input str>,
-    total_time: u32,
-    you: ValvePosition<'input>,
-    elephant: Option<ValvePosition<'input>>,
-) -> u32 {
-    let cache_key = (closed.clone(), total_time, you, elephant);
-
-    if let Ok(cache) = cache.read() {
-        if let Some(val) = cache.get(&cache_key) {
-            return *val;
-        }
-    }
+/// How much pressure a team releases, and the valves each agent opens.
+type Split = (u32, Vec<ValveSet>);
 
-    let consider_you = |cache: Arc<RwLock<Cache<'input>>>,
-                        closed: BTreeSet<&'input str>,
-                        elephant: Option<ValvePosition<'input>>| {
-        adjacency_matrix
-            .get(you.valve)
-            .unwrap()
-            .par_iter()
-            .filter(|(valve, distance)| {
-                closed.contains(*valve) && you.open_time_end + *distance < total_time
-            })
-            .fold(
-                || 0,
-                |max, (valve, distance)| {
-                    let mut new_closed = closed.clone();
-                    new_closed.remove(valve);
-                    let open_time_end = you.open_time_end + distance;
-                    let time_remaining = total_time - open_time_end;
-                    let flow = flows.get(valve).unwrap();
-
-                    max.max(
-                        flow * time_remaining
-                            + max_pressure_inner(
-                                Arc::clone(&cache),
-                                flows,
-                                adjacency_matrix,
-                                new_closed,
-                                total_time,
-                                ValvePosition {
-                                    valve,
-                                    open_time_end,
-                                },
-                                elephant,
-                            ),
-                    )
-                },
-            )
-            .reduce(|| 0, |acc, val| acc.max(val))
+/// Shares valves out between agents so that together they release as much
+/// pressure as possible, given the most each of them can release opening each
+/// set of valves.
+fn best_split(tables: &[Scores]) -> Vec<ValveSet> {
+    let Some((last, tables)) = tables.split_last() else {
+        return Vec::new();
     };
 
-    let max = if let Some(elephant) = elephant {
-        adjacency_matrix
-            .get(elephant.valve)
-            .unwrap()
-            .par_iter()
-            .filter(|(valve, distance)| {
-                closed.contains(*valve) && elephant.open_time_end + *distance < total_time
-            })
-            .fold(
-                || 0,
-                |max, (valve, distance)| {
-                    let mut new_closed = closed.clone();
-                    new_closed.remove(valve);
-                    let open_time_end = elephant.open_time_end + distance;
-                    let time_remaining = total_time - open_time_end;
-                    let flow = flows.get(valve).unwrap();
-
-                    max.max(
-                        flow * time_remaining
-                            + consider_you(
-                                Arc::clone(&cache),
-                                new_closed,
-                                Some(ValvePosition {
-                                    valve,
-                                    open_time_end,
-                                }),
-                            ),
-                    )
-                },
-            )
-            .reduce(|| 0, |acc, val| acc.max(val))
-    } else {
-        consider_you(Arc::clone(&cache), closed, None)
-    };
+    // Every way of sharing valves out between all but the last agent, keeping
+    // the best for each set of valves opened between them.
+    let mut combined: HashMap<ValveSet, Split> = HashMap::from([(0, (0, Vec::new()))]);
+    for table in tables {
+        let mut next: HashMap<ValveSet, Split> = HashMap::new();
+        for (opened, (released, split)) in &combined {
+            for (agent_opened, agent_released) in table {
+                if opened & agent_opened != 0 {
+                    continue;
+                }
 
-    if let Ok(mut cache) = cache.write() {
-        cache
-            .entry(cache_key)
-            .and_modify(|prev| {
-                if max >= *prev {
-                    *prev = max;
+                let released = released + agent_released;
+                let opened = opened | agent_opened;
+                if next.get(&opened).is_none_or(|(best, _)| released > *best) {
+                    let mut split = split.clone();
+                    split.push(*agent_opened);
+                    next.insert(opened, (released, split));
                 }
-            })
-            .or_insert(max);
+            }
+        }
+        combined = next;
     }
 
-    max
+    // The last agent only needs pairing up with the best of those. Going
+    // through both from the most pressure down means most pairs never need
+    // looking at.
+    let mut combined: Vec<(ValveSet, Split)> = combined.into_iter().collect();
+    combined.sort_by_key(|(_, (released, _))| std::cmp::Reverse(*released));
+    let mut last: Vec<(ValveSet, u32)> = last.iter().map(|(k, v)| (*k, *v)).collect();
+    last.sort_by_key(|(_, released)| std::cmp::Reverse(*released));
+    let most = last.first().map_or(0, |(_, released)| *released);
+
+    let mut best: Option<Split> = None;
+    for (opened, (released, split)) in &combined {
+        let best_released = best.as_ref().map_or(0, |(best, _)| *best);
+        if best.is_some() && released + most <= best_released {
+            break;
+        }
+
+        for (last_opened, last_released) in &last {
+            if best.is_some() && released + last_released <= best_released {
+                break;
+            }
+
+            if opened & last_opened == 0 {
+                let mut split = split.clone();
+                split.push(*last_opened);
+                best = Some((released + last_released, split));
+                break;
+            }
+        }
+    }
+
+    best.unwrap().1
 }
 
-#[cfg(not(feature = "multiprocessing"))]
-fn max_pressure<'input>(
-    flows: &HashMap<&'input str, u32>,
-    tunnels: &'input HashMap<&'input str, Vec<String>>,
-    time_remaining: u32,
-    you: ValvePosition<'input>,
-    elephant: Option<ValvePosition<'input>>,
-) -> u32 {
-    let adjacency_matrix = get_adjacency_matrix(&flows, &tunnels);
+/// The order to open `valves` in, starting from `from`, that releases the
+/// most pressure.
+fn best_order(
+    graph: &Graph,
+    valves: ValveSet,
+    total_time: u32,
+    from: ValvePosition,
+) -> (u32, Vec<ValvePosition>) {
+    (0..graph.useful)
+        .filter(|valve| valves & (1 << valve) != 0)
+        .filter_map(|valve| {
+            let open_time_end = graph.open_time(from, valve, total_time)?;
+            let to = ValvePosition {
+                valve,
+                open_time_end,
+            };
+
+            let (released, mut order) = best_order(graph, valves & !(1 << valve), total_time, to);
+            order.insert(0, to);
+
+            Some((
+                released + graph.flows[valve] * (total_time - open_time_end),
+                order,
+            ))
+        })
+        .max_by_key(|(released, _)| *released)
+        .unwrap_or_default()
+}
 
-    let closed: BTreeSet<&str> = flows
-        .iter()
-        .filter_map(|(valve, flow)| if *flow > 0 { Some(*valve) } else { None })
-        .collect();
+/// The most pressure an agent can release opening each set of valves.
+type Scores = HashMap<ValveSet, u32>;
 
-    let mut cache = HashMap::new();
+/// Where the search keeps track of the most pressure released for each set of
+/// valves opened, shared between all its branches.
+trait Cache: Default + Sync {
+    fn record(&self, opened: ValveSet, released: u32);
+    fn into_scores(self) -> Scores;
+}
 
-    max_pressure_inner(
-        &mut cache,
-        flows,
-        &adjacency_matrix,
-        closed,
-        time_remaining,
-        you,
-        elephant,
-    )
+impl Cache for Mutex<Scores> {
+    fn record(&self, opened: ValveSet, released: u32) {
+        let mut scores = self.lock().unwrap();
+        let best = scores.entry(opened).or_insert(released);
+        *best = released.max(*best);
+    }
+
+    fn into_scores(self) -> Scores {
+        self.into_inner().unwrap()
+    }
+}
+
+#[cfg(feature = "multiprocessing")]
+impl Cache for DashMap<ValveSet, u32> {
+    fn record(&self, opened: ValveSet, released: u32) {
+        let mut best = self.entry(opened).or_insert(released);
+        *best = released.max(*best);
+    }
+
+    fn into_scores(self) -> Scores {
+        self.into_iter().collect()
+    }
+}
+
+/// How the search works through the branches from each position.
+trait Strategy: Sync {
+    type Cache: Cache;
+
+    fn for_each<T: Send>(&self, branches: Vec<T>, f: impl Fn(T) + Sync + Send);
+}
+
+/// Works through branches one at a time, so its cache's lock is never
+/// contended.
+struct Sequential;
+
+impl Strategy for Sequential {
+    type Cache = Mutex<Scores>;
+
+    fn for_each<T: Send>(&self, branches: Vec<T>, f: impl Fn(T) + Sync + Send) {
+        branches.into_iter().for_each(f);
+    }
 }
 
-#[cfg(not(feature = "multiprocessing"))]
-fn max_pressure_inner<'input>(
-    cache: &mut Cache<'input>,
-    flows: &HashMap<&'input str, u32>,
-    adjacency_matrix: &'input HashMap<&'input str, HashMap<&'input str, u32>>,
-    closed: BTreeSet<&'input str>,
+/// Spreads branches across threads, sharing a cache split into shards so they
+/// rarely wait on each other.
+#[cfg(feature = "multiprocessing")]
+struct Parallel;
+
+#[cfg(feature = "multiprocessing")]
+impl Strategy for Parallel {
+    type Cache = DashMap<ValveSet, u32>;
+
+    fn for_each<T: Send>(&self, branches: Vec<T>, f: impl Fn(T) + Sync + Send) {
+        branches.into_par_iter().for_each(f);
+    }
+}
+
+fn max_pressure<S: Strategy>(
+    strategy: &S,
+    graph: &Graph,
     total_time: u32,
-    you: ValvePosition<'input>,
-    elephant: Option<ValvePosition<'input>>,
-) -> u32 {
-    let cache_key = (closed.clone(), total_time, you, elephant);
+    start: ValvePosition,
+) -> Scores {
+    let cache = S::Cache::default();
 
-    if let Some(val) = cache.get(&cache_key) {
-        return *val;
+    max_pressure_inner(strategy, &cache, graph, 0, 0, total_time, start);
+
+    cache.into_scores()
+}
+
+fn max_pressure_inner<S: Strategy>(
+    strategy: &S,
+    cache: &S::Cache,
+    graph: &Graph,
+    opened: ValveSet,
+    released: u32,
+    total_time: u32,
+    position: ValvePosition,
+) {
+    cache.record(opened, released);
+
+    let branches: Vec<(usize, u32)> = (0..graph.useful)
+        .filter(|valve| opened & (1 << valve) == 0)
+        .filter_map(|valve| Some((valve, graph.open_time(position, valve, total_time)?)))
+        .collect();
+
+    strategy.for_each(branches, |(valve, open_time_end)| {
+        max_pressure_inner(
+            strategy,
+            cache,
+            graph,
+            opened | (1 << valve),
+            released + graph.flows[valve] * (total_time - open_time_end),
+            total_time,
+            ValvePosition {
+                valve,
+                open_time_end,
+            },
+        );
+    });
+}
+
+#[test]
+fn plan_works() {
+    let input = "\
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    assert_eq!(main(input), (1651, 1707));
+
+    let (alone, with_elephant) = plans(input, &Params::default());
+    assert!(alone.to_string().starts_with(
+        "\
+== Minute 1 ==
+No valves are open.
+You move to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve CC.
+"
+    ));
+    assert_eq!(alone.released_per_minute()[21], 79);
+    assert_eq!(
+        alone.open_valves(30),
+        vec!["BB", "CC", "DD", "EE", "HH", "JJ"]
+    );
+
+    assert_eq!(with_elephant.minutes(), 26);
+    assert_eq!(
+        with_elephant.open_valves(12),
+        vec!["BB", "CC", "DD", "EE", "HH", "JJ"]
+    );
+    assert!(with_elephant
+        .to_string()
+        .contains("The elephant opens valve"));
+
+    for (agents, minutes, released) in [
+        ("AA:30", 30, 1651),
+        ("AA:26 AA:26 AA:26", 26, 1794),
+        ("AA:26 JJ:20 HH:10", 26, 1563),
+    ] {
+        let params = Params::with_overrides(&[("agents".to_string(), agents.to_string())]).unwrap();
+        let team = plans(input, &params).1;
+
+        assert_eq!(team.minutes(), minutes, "{agents}");
+        assert_eq!(team.released(), released, "{agents}");
     }
 
-    let consider_you = |cache: &mut Cache<'input>,
-                        closed: BTreeSet<&'input str>,
-                        elephant: Option<ValvePosition<'input>>| {
-        adjacency_matrix
-            .get(you.valve)
-            .unwrap()
-            .iter()
-            .filter(|(valve, distance)| {
-                closed.contains(*valve) && you.open_time_end + *distance < total_time
-            })
-            .fold(0, |max, (valve, distance)| {
-                let mut new_closed = closed.clone();
-                new_closed.remove(valve);
-                let open_time_end = you.open_time_end + distance;
-                let time_remaining = total_time - open_time_end;
-                let flow = flows.get(valve).unwrap();
-
-                max.max(
-                    flow * time_remaining
-                        + max_pressure_inner(
-                            cache,
-                            flows,
-                            adjacency_matrix,
-                            new_closed,
-                            total_time,
-                            ValvePosition {
-                                valve,
-                                open_time_end,
-                            },
-                            elephant,
-                        ),
-                )
-            })
+    // The elephant starting at JJ with less time opens it as soon as it can.
+    let params =
+        Params::with_overrides(&[("agents".to_string(), "AA:26 JJ:20".to_string())]).unwrap();
+    let team = plans(input, &params).1;
+    assert!(!team.open_valves(7).contains(&"JJ"));
+    assert!(team.open_valves(8).contains(&"JJ"));
+
+    assert!(Agent::from_str("AA26").is_err());
+    assert!(Params::with_overrides(&[("agents".to_string(), " ".to_string())]).is_err());
+
+    let idle = Plan {
+        actions: Vec::new(),
+        flows: HashMap::new(),
     };
+    assert_eq!((idle.minutes(), idle.released()), (0, 0));
+}
 
-    let max = if let Some(elephant) = elephant {
-        adjacency_matrix
-            .get(elephant.valve)
-            .unwrap()
-            .iter()
-            .filter(|(valve, distance)| {
-                closed.contains(*valve) && elephant.open_time_end + *distance < total_time
-            })
-            .fold(0, |max, (valve, distance)| {
-                let mut new_closed = closed.clone();
-                new_closed.remove(valve);
-                let open_time_end = elephant.open_time_end + distance;
-                let time_remaining = total_time - open_time_end;
-                let flow = flows.get(valve).unwrap();
-
-                max.max(
-                    flow * time_remaining
-                        + consider_you(
-                            cache,
-                            new_closed,
-                            Some(ValvePosition {
-                                valve,
-                                open_time_end,
-                            }),
-                        ),
-                )
-            })
-    } else {
-        consider_you(cache, closed, None)
+#[test]
+fn graph_works() {
+    let input = "\
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    let graph = Graph::from_str(input).unwrap();
+    assert_eq!(graph.useful, 6);
+    assert!(graph.flows[..graph.useful].iter().all(|flow| *flow > 0));
+
+    let (aa, hh, jj) = (graph.index("AA"), graph.index("HH"), graph.index("JJ"));
+    assert_eq!(graph.distances[aa][hh], 5);
+    assert_eq!(graph.distances[jj][hh], 7);
+    assert_eq!(
+        graph
+            .route(aa, hh)
+            .into_iter()
+            .map(|valve| graph.names[valve].as_str())
+            .collect::<Vec<_>>(),
+        vec!["DD", "EE", "FF", "GG", "HH"]
+    );
+
+    let dot = dot(input, &Params::default());
+    assert!(dot.starts_with("graph valves {\n"));
+    assert!(dot.contains("    AA [shape=doublecircle];\n"));
+    assert!(dot.contains("    HH [label=\"HH\\nflow 22\", shape=circle];\n"));
+    assert!(dot.contains("    EE -- HH [label=\"3\"];\n"));
+    assert!(!dot.contains("GG"));
+    assert_eq!(dot.matches(" -- ").count(), 7);
+
+    assert!(Graph::from_str("Valve AA has flow rate=0; tunnel leads to valve BB").is_err());
+}
+
+#[test]
+fn strategies_work() {
+    let input = "\
+Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    let graph = Graph::from_str(input).unwrap();
+    let start = ValvePosition {
+        valve: graph.index("AA"),
+        open_time_end: 0,
     };
 
-    cache
-        .entry(cache_key)
-        .and_modify(|prev| {
-            if max >= *prev {
-                *prev = max;
-            }
-        })
-        .or_insert(max);
+    let scores = max_pressure(&Sequential, &graph, 30, start);
+    assert_eq!(scores.get(&0), Some(&0));
+    assert_eq!(scores.get(&(1 << graph.index("DD"))), Some(&(20 * 28)));
+    assert_eq!(scores.values().max(), Some(&1651));
+
+    #[cfg(feature = "multiprocessing")]
+    assert_eq!(max_pressure(&Parallel, &graph, 30, start), scores);
 
-    max
+    let params = Params::with_overrides(&[("parallel".to_string(), "false".to_string())]).unwrap();
+    assert_eq!(main_with_params(input, &params), (1651, 1707));
+    assert_eq!(
+        Params::with_overrides(&[("parallel".to_string(), "true".to_string())]).is_ok(),
+        cfg!(feature = "multiprocessing")
+    );
 }
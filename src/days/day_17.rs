@@ -1,42 +1,214 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
+    str::FromStr,
 };
 
-use crate::coord::Coord;
+use crate::{
+    coord::Coord,
+    image::{Canvas, Framed, Raster, Recorder, Rgb, TileMap},
+    params::{parse_param, unknown_param, Parameters},
+    range::Range,
+    visualize::Visualizer,
+};
+
+/// The rocks from the puzzle, in the order they fall.
+const PUZZLE_ROCKS: &str = "\
+####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##";
+
+/// How many rocks are dropped looking for the tower to start repeating
+/// before giving up on it.
+const CYCLE_SEARCH_ROCKS: usize = 100_000;
+
+/// How far below the top of the tower rocks can still reach before the
+/// tower's surface is too deep to be worth comparing.
+const MAX_SURFACE_DEPTH: u64 = 128;
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub part1_rocks: usize,
+    pub part2_rocks: usize,
+    /// The rocks that fall, in order, before starting again from the first.
+    pub rocks: Vec<RockShape>,
+    pub chamber_width: u64,
+    /// How far from the left wall each rock appears.
+    pub spawn_x: u64,
+    /// How many empty rows are left between the tower and each new rock.
+    pub spawn_y: u64,
+}
 
-const SPAWN_X: u64 = 2;
-const TOTAL_ROCKS: usize = 1000000000000;
-const CYCLE_CHECK_HEIGHT_HEURISTIC: usize = 10;
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            part1_rocks: 2022,
+            part2_rocks: 1000000000000,
+            rocks: parse_rocks(PUZZLE_ROCKS).unwrap(),
+            chamber_width: 7,
+            spawn_x: 2,
+            spawn_y: 3,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "part1_rocks" => self.part1_rocks = parse_param(key, value)?,
+            "part2_rocks" => self.part2_rocks = parse_param(key, value)?,
+            "rocks" => {
+                self.rocks = parse_rocks(value)
+                    .map_err(|e| format!("invalid value for parameter {key}: {value} ({e})"))?
+            }
+            "chamber_width" => self.chamber_width = parse_param(key, value)?,
+            "spawn_x" => self.spawn_x = parse_param(key, value)?,
+            "spawn_y" => self.spawn_y = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.rocks.is_empty() {
+            return Err("there are no rocks to drop".to_string());
+        }
+
+        for (i, rock) in self.rocks.iter().enumerate() {
+            if self.spawn_x + rock.width > self.chamber_width {
+                return Err(format!(
+                    "rock {} is {} wide, so it doesn't fit {} from the wall of a chamber {} wide",
+                    i + 1,
+                    rock.width,
+                    self.spawn_x,
+                    self.chamber_width
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (u64, u64) {
-    let jet_pattern: Vec<JetDirection> = input
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u64, u64) {
+    heights(input, params).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// The height of the tower after each part's rocks. Fails if there are too
+/// many rocks to drop one by one and the tower never starts repeating.
+pub fn heights(input: &str, params: &Params) -> Result<(u64, u64), String> {
+    let total_rocks = params.part1_rocks.max(params.part2_rocks);
+    let chamber = simulate(input, params, total_rocks, true, |_| true);
+
+    // println!("{}", &chamber);
+    Ok((
+        chamber.get_height(params.part1_rocks)?,
+        chamber.get_height(params.part2_rocks)?,
+    ))
+}
+
+pub fn visualize(input: &str, params: &Params, visualizer: &mut Visualizer) {
+    simulate(input, params, params.part1_rocks, false, |view| {
+        visualizer.frame(view)
+    });
+}
+
+pub fn export(input: &str, params: &Params, recorder: &mut Recorder) {
+    // The tower only grows, so a first run finds how tall the picture needs
+    // to be for every frame to be the same size.
+    let mut frames = 0;
+    let mut bounds = None;
+    simulate(input, params, params.part1_rocks, false, |view| {
+        if view.rock.is_none() {
+            frames += 1;
+            bounds = Some(view.bounds());
+        }
+        true
+    });
+
+    let Some((x, y)) = bounds else {
+        return;
+    };
+    recorder.expect_frames(frames);
+    simulate(input, params, params.part1_rocks, false, |view| {
+        view.rock.is_some() || recorder.frame(&Framed { state: view, x, y })
+    });
+}
+
+/// Parses a list of rocks drawn with `#` and `.`, top row first. Rocks are
+/// separated by blank lines, or when it's all on one line (as with
+/// `--param`) by spaces, with `/` between rows: `#### .#./###/.#.`.
+pub fn parse_rocks(s: &str) -> Result<Vec<RockShape>, String> {
+    if s.contains('\n') {
+        s.split("\n\n").map(str::parse).collect()
+    } else {
+        s.split_whitespace().map(str::parse).collect()
+    }
+}
+
+/// Drops `total_rocks` rocks into the chamber. With `find_cycle`, it stops
+/// as soon as the tower is seen to repeat, leaving the rest of its height to
+/// [`Chamber::get_height`], or after [`CYCLE_SEARCH_ROCKS`] if it never does.
+fn simulate(
+    input: &str,
+    params: &Params,
+    total_rocks: usize,
+    find_cycle: bool,
+    mut on_step: impl FnMut(&ChamberView) -> bool,
+) -> Chamber {
+    params.validate().unwrap_or_else(|e| panic!("{e}"));
+
+    let jets: Vec<JetDirection> = input
         .chars()
         .map(|c| JetDirection::try_from(c).unwrap())
         .collect();
-    let mut jets = jet_pattern.iter().copied().cycle();
-
-    let mut chamber = Chamber::new(7);
-
-    for (i, shape) in RockShape::all()
-        .into_iter()
-        .cycle()
-        .take(TOTAL_ROCKS)
-        .enumerate()
-    {
+    let mut jet_index = 0;
+
+    let mut chamber = Chamber::new(params.chamber_width);
+    let mut seen = HashMap::new();
+    let total_rocks = if find_cycle {
+        total_rocks.min(CYCLE_SEARCH_ROCKS)
+    } else {
+        total_rocks
+    };
+
+    'rocks: for i in 0..total_rocks {
+        let shape_index = i % params.rocks.len();
         let mut rock = Rock {
-            shape,
-            pos: Coord(SPAWN_X, chamber.top + 3),
+            shape: &params.rocks[shape_index],
+            pos: Coord(params.spawn_x, chamber.top + params.spawn_y),
         };
 
         loop {
-            if let Some(x) = match jets.next().unwrap() {
+            let jet = jets[jet_index];
+            jet_index = (jet_index + 1) % jets.len();
+
+            if let Some(x) = match jet {
                 JetDirection::Left => rock.pos.0.checked_sub(1),
                 JetDirection::Right => rock.pos.0.checked_add(1),
             } {
                 let prev = rock.pos.0;
                 rock.pos.0 = x;
-                if rock.pos.0 + rock.shape.width() > chamber.width
+                if rock.pos.0 + rock.shape.width > chamber.width
                     || rock.coords().any(|c| chamber.filled.contains(&c))
                 {
                     rock.pos.0 = prev;
@@ -49,77 +221,53 @@ pub fn main(input: &str) -> (u64, u64) {
 
             rock.pos.1 -= 1;
 
-            if rock.pos.1 > chamber.top {
-                continue;
-            }
-
-            if rock.coords().any(|c| chamber.filled.contains(&c)) {
+            if rock.pos.1 <= chamber.top && rock.coords().any(|c| chamber.filled.contains(&c)) {
                 rock.pos.1 += 1;
                 break;
             }
+
+            if !on_step(&ChamberView::new(&chamber, Some(rock))) {
+                break 'rocks;
+            }
         }
 
         for c in rock.coords() {
-            chamber.tops[c.0 as usize] = chamber.tops[c.0 as usize].max(c.1);
             let new = chamber.filled.insert(c);
             if !new {
                 panic!("overlap detected! {:?}, {:?}", c, chamber.filled);
             }
         }
-        chamber.top = chamber.top.max(rock.pos.1 + rock.shape.height());
-        let i_min = chamber.tops.iter().copied().min().unwrap();
-        let top_deltas: Vec<u64> = chamber.tops.iter().copied().map(|y| y - i_min).collect();
-        let existing = chamber.top_deltas_to_rock_index.get(&top_deltas).copied();
-        chamber.top_deltas_to_rock_index.insert(top_deltas, i);
-        chamber.rock_index_to_tops.push(chamber.tops.clone());
-
-        if let Some(j) = existing {
-            let cycle_size = i - j;
-            if cycle_size % RockShape::all().len() == 0 {
-                if let Some((start, end)) = chamber.cycle {
-                    let offset = i - end;
-                    if start > j || j - start != offset {
-                        chamber.cycle = None;
-                    } else if offset >= CYCLE_CHECK_HEIGHT_HEURISTIC {
-                        let i_max = chamber.tops.iter().copied().max().unwrap();
-                        let start_tops = &chamber.rock_index_to_tops[start];
-                        let end_tops = &chamber.rock_index_to_tops[end];
-                        let end_bot = end_tops.iter().copied().min().unwrap();
-                        let start_bot = start_tops.iter().copied().min().unwrap();
-                        let cycle_diff = end_bot - start_bot;
-
-                        if (end_bot..=i_max).rev().all(|y| {
-                            (0..chamber.width).all(|x| {
-                                !chamber.filled.contains(&Coord(x, y))
-                                    || chamber.filled.contains(&Coord(x, y - cycle_diff))
-                            })
-                        }) {
-                            break;
-                        } else {
-                            chamber.cycle = None;
-                        }
-                    }
-                } else {
-                    chamber.cycle = Some((j, i));
-                }
+        chamber.top = chamber.top.max(rock.pos.1 + rock.shape.height);
+        chamber.heights.push(chamber.top);
+
+        if find_cycle {
+            // What happens next only depends on which rock and jet come next
+            // and on the part of the chamber that rocks can still reach, so
+            // once that repeats, so will the tower.
+            let previous = chamber
+                .surface()
+                .and_then(|surface| seen.insert((shape_index, jet_index, surface), i));
+            if let Some(j) = previous {
+                chamber.cycle = Some((j, i));
+                break;
             }
         }
+
+        if !on_step(&ChamberView::new(&chamber, None)) {
+            break;
+        }
     }
 
-    // println!("{}", &chamber);
-    (
-        chamber.get_height(2022).unwrap(),
-        chamber.get_height(TOTAL_ROCKS).unwrap(),
-    )
+    chamber
 }
 
 struct Chamber {
     width: u64,
     filled: HashSet<Coord<u64>>,
     top: u64,
-    tops: Vec<u64>,
-    top_deltas_to_rock_index: HashMap<Vec<u64>, usize>,
-    rock_index_to_tops: Vec<Vec<u64>>,
+    /// The height of the tower after each rock.
+    heights: Vec<u64>,
+    /// The indices of two rocks after which the tower repeats.
     cycle: Option<(usize, usize)>,
 }
 
@@ -129,35 +277,79 @@ impl Chamber {
             width,
             filled: HashSet::new(),
             top: 0,
-            tops: vec![0; width as usize],
-            rock_index_to_tops: Vec::new(),
-            top_deltas_to_rock_index: HashMap::new(),
+            heights: Vec::new(),
             cycle: None,
         }
     }
 
-    fn get_height(&self, rock_num: usize) -> Option<u64> {
-        self.get_top(rock_num - 1).map(|top| top + 1)
+    /// The empty cells a falling rock could still get to, found by filling
+    /// down and sideways from the row above the tower, packed into bits a row
+    /// at a time from the top down. Rocks only ever touch these cells and the
+    /// ones around them, so two towers with the same surface carry on the
+    /// same way however deep it goes.
+    ///
+    /// Returns `None` if rocks can get more than [`MAX_SURFACE_DEPTH`] rows
+    /// down.
+    fn surface(&self) -> Option<Vec<u64>> {
+        let mut bits: Vec<u64> = Vec::new();
+        let mut stack: Vec<Coord<u64>> = (0..self.width).map(|x| Coord(x, self.top)).collect();
+
+        while let Some(c) = stack.pop() {
+            let depth = self.top - c.1;
+            let bit = depth * self.width + c.0;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            if bits.get(word).is_some_and(|w| w & mask != 0) || self.filled.contains(&c) {
+                continue;
+            }
+            if depth > MAX_SURFACE_DEPTH {
+                return None;
+            }
+
+            if word >= bits.len() {
+                bits.resize(word + 1, 0);
+            }
+            bits[word] |= mask;
+
+            if c.0 > 0 {
+                stack.push(Coord(c.0 - 1, c.1));
+            }
+            if c.0 + 1 < self.width {
+                stack.push(Coord(c.0 + 1, c.1));
+            }
+            if c.1 > 0 {
+                stack.push(Coord(c.0, c.1 - 1));
+            }
+        }
+
+        Some(bits)
+    }
+
+    /// How much the tower grew from after rock `from` to after rock `to`.
+    fn rise(&self, from: usize, to: usize) -> u64 {
+        self.heights[to] - self.heights[from]
     }
 
-    fn get_top(&self, rock_index: usize) -> Option<u64> {
-        if let Some(tops) = self.rock_index_to_tops.get(rock_index) {
-            tops.iter().copied().max().map(|y| y)
+    /// The height of the tower after `rock_num` rocks, worked out from the
+    /// cycle if the simulation stopped before then.
+    fn get_height(&self, rock_num: usize) -> Result<u64, String> {
+        let Some(rock_index) = rock_num.checked_sub(1) else {
+            return Ok(0);
+        };
+
+        if let Some(height) = self.heights.get(rock_index) {
+            Ok(*height)
         } else if let Some((start, end)) = self.cycle {
             let rock_index_after_start = rock_index - start;
             let cycle_size = end - start;
             let num_cycles = rock_index_after_start / cycle_size;
             let rem_after_start = rock_index_after_start % cycle_size;
 
-            let cycle_start_top = self.get_top(start)?;
-            let cycle_end_top = self.get_top(end)?;
-            let cycle_delta_height = cycle_end_top - cycle_start_top;
-
-            let rem_delta_height = self.get_top(start + rem_after_start)?;
-
-            Some((cycle_delta_height * num_cycles as u64) + rem_delta_height)
+            Ok(self.rise(start, end) * num_cycles as u64 + self.heights[start + rem_after_start])
         } else {
-            None
+            Err(format!(
+                "the tower didn't start repeating within {} rocks, so the height after {rock_num} can't be worked out",
+                self.heights.len()
+            ))
         }
     }
 }
@@ -203,78 +395,184 @@ impl Display for Chamber {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Rock {
-    shape: RockShape,
-    pos: Coord<u64>,
+const VIEW_ROWS: u64 = 40;
+
+/// The chamber along with the rock currently falling through it. Printed,
+/// it shows just the top of the chamber, with the rock drawn as `@` like the
+/// puzzle's diagrams. As an image, it shows the whole chamber.
+struct ChamberView<'a> {
+    chamber: &'a Chamber,
+    rock: Option<Rock<'a>>,
 }
 
-impl Rock {
-    fn coords(self) -> impl Iterator<Item = Coord<u64>> {
-        self.shape.coords().into_iter().map(move |c| *c + self.pos)
+impl<'a> ChamberView<'a> {
+    fn new(chamber: &'a Chamber, rock: Option<Rock<'a>>) -> Self {
+        ChamberView { chamber, rock }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum RockShape {
-    Horizontal,
-    Plus,
-    BackwardsL,
-    Vertical,
-    Square,
+impl ChamberView<'_> {
+    fn falling(&self) -> HashSet<Coord<u64>> {
+        self.rock.into_iter().flat_map(Rock::coords).collect()
+    }
+
+    fn rows(&self, falling: &HashSet<Coord<u64>>) -> (u64, u64) {
+        let top = falling
+            .iter()
+            .map(|c| c.1)
+            .max()
+            .unwrap_or(0)
+            .max(self.chamber.top + 2);
+
+        ((top + 1).saturating_sub(VIEW_ROWS), top)
+    }
 }
 
-impl RockShape {
-    fn all() -> [RockShape; 5] {
-        [
-            RockShape::Horizontal,
-            RockShape::Plus,
-            RockShape::BackwardsL,
-            RockShape::Vertical,
-            RockShape::Square,
-        ]
+/// Rows are numbered downwards from 0 at the floor, so that the top of the
+/// chamber is drawn at the top of the image.
+impl TileMap for ChamberView<'_> {
+    fn bounds(&self) -> (Range, Range) {
+        let (_, top) = self.rows(&self.falling());
+
+        (
+            Range(0, self.chamber.width as i64 - 1),
+            Range(-(top as i64), 0),
+        )
     }
 
-    fn coords(self) -> &'static [Coord<u64>] {
-        match self {
-            RockShape::Horizontal => &[Coord(0, 0), Coord(1, 0), Coord(2, 0), Coord(3, 0)],
-            RockShape::Plus => &[
-                Coord(1, 0),
-                Coord(0, 1),
-                Coord(1, 1),
-                Coord(2, 1),
-                Coord(1, 2),
-            ],
-            RockShape::BackwardsL => &[
-                Coord(0, 0),
-                Coord(1, 0),
-                Coord(2, 0),
-                Coord(2, 1),
-                Coord(2, 2),
-            ],
-            RockShape::Vertical => &[Coord(0, 0), Coord(0, 1), Coord(0, 2), Coord(0, 3)],
-            RockShape::Square => &[Coord(0, 0), Coord(0, 1), Coord(1, 0), Coord(1, 1)],
+    fn color_at(&self, x: i64, y: i64) -> Rgb {
+        let coord = Coord(x as u64, (-y) as u64);
+        if self
+            .rock
+            .is_some_and(|rock| rock.coords().any(|c| c == coord))
+        {
+            Rgb::GOLD
+        } else if self.chamber.filled.contains(&coord) {
+            Rgb::GREY
+        } else {
+            Rgb::BLACK
         }
     }
+}
+
+impl Raster for ChamberView<'_> {
+    fn raster(&self) -> Canvas {
+        let (x, y) = self.bounds();
+        self.raster_within(x, y)
+    }
+}
+
+impl Display for ChamberView<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let falling = self.falling();
+        let (bottom, top) = self.rows(&falling);
+
+        for y in (bottom..=top).rev() {
+            write!(f, "{}\t|", y)?;
+            for x in 0..self.chamber.width {
+                if falling.contains(&Coord(x, y)) {
+                    write!(f, "@")?;
+                } else if self.chamber.filled.contains(&Coord(x, y)) {
+                    write!(f, "#")?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f, "|")?;
+        }
+
+        if bottom == 0 {
+            write!(f, "\t+")?;
+            for _ in 0..self.chamber.width {
+                write!(f, "-")?;
+            }
+            writeln!(f, "+")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rock<'a> {
+    shape: &'a RockShape,
+    pos: Coord<u64>,
+}
+
+impl<'a> Rock<'a> {
+    fn coords(self) -> impl Iterator<Item = Coord<u64>> + 'a {
+        self.shape.cells.iter().map(move |c| *c + self.pos)
+    }
+}
 
-    fn width(self) -> u64 {
-        match self {
-            RockShape::Horizontal => 4,
-            RockShape::Plus => 3,
-            RockShape::BackwardsL => 3,
-            RockShape::Vertical => 1,
-            RockShape::Square => 2,
+/// The cells of a rock, relative to its bottom left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RockShape {
+    cells: Vec<Coord<u64>>,
+    width: u64,
+    height: u64,
+}
+
+impl FromStr for RockShape {
+    type Err = String;
+
+    /// Reads a rock drawn with `#` and `.`, top row first, with rows on
+    /// separate lines or separated by `/`. Empty rows and columns around the
+    /// rock are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s
+            .split(['\n', '/'])
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        let mut art_cells = Vec::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '#' => art_cells.push((x as u64, row as u64)),
+                    '.' => {}
+                    c => return Err(format!("invalid cell {c} in rock {s}")),
+                }
+            }
+        }
+
+        if art_cells.is_empty() {
+            return Err(format!("rock {s} has no cells"));
         }
+
+        let min_x = art_cells.iter().map(|(x, _)| *x).min().unwrap();
+        let max_x = art_cells.iter().map(|(x, _)| *x).max().unwrap();
+        let min_row = art_cells.iter().map(|(_, row)| *row).min().unwrap();
+        let max_row = art_cells.iter().map(|(_, row)| *row).max().unwrap();
+
+        Ok(RockShape {
+            cells: art_cells
+                .into_iter()
+                .map(|(x, row)| Coord(x - min_x, max_row - row))
+                .collect(),
+            width: max_x - min_x + 1,
+            height: max_row - min_row + 1,
+        })
     }
+}
 
-    fn height(self) -> u64 {
-        match self {
-            RockShape::Horizontal => 1,
-            RockShape::Plus => 3,
-            RockShape::BackwardsL => 3,
-            RockShape::Vertical => 4,
-            RockShape::Square => 2,
+impl Display for RockShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                if self.cells.contains(&Coord(x, y)) {
+                    write!(f, "#")?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            if y > 0 {
+                writeln!(f)?;
+            }
         }
+
+        Ok(())
     }
 }
 
@@ -295,3 +593,84 @@ impl TryFrom<char> for JetDirection {
         }
     }
 }
+
+#[test]
+fn custom_rocks_work() {
+    let jets = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+    assert_eq!(main(jets), (3068, 1514285714288));
+
+    // Pictures show the whole tower, and a little room above it.
+    let chamber = simulate(jets, &Params::default(), 2022, false, |_| true);
+    let canvas = ChamberView::new(&chamber, None).raster();
+    assert_eq!((canvas.width(), canvas.height()), (7, 3068 + 3));
+
+    let rock: RockShape = "..#/..#/###".parse().unwrap();
+    assert_eq!(rock.to_string(), "..#\n..#\n###");
+    assert_eq!(Params::default().rocks[2], rock);
+    assert_eq!(
+        "..../.##./.##.".parse(),
+        Ok(Params::default().rocks[4].clone())
+    );
+    assert!("#.x".parse::<RockShape>().is_err());
+    assert!("../..".parse::<RockShape>().is_err());
+
+    let params = Params {
+        part1_rocks: 3000,
+        part2_rocks: 3000,
+        rocks: parse_rocks("### #/#/# ##/#. #.#/###").unwrap(),
+        chamber_width: 5,
+        spawn_x: 1,
+        spawn_y: 2,
+    };
+    let extrapolated = simulate(jets, &params, 3000, true, |_| true);
+    assert!(extrapolated.cycle.is_some());
+    assert_eq!(
+        extrapolated.get_height(3000),
+        simulate(jets, &params, 3000, false, |_| true).get_height(3000)
+    );
+
+    assert!(Params {
+        chamber_width: 5,
+        ..Params::default()
+    }
+    .validate()
+    .is_err());
+}
+
+#[test]
+fn cycles_match_simulation() {
+    let jets = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+
+    // Narrow rocks in a wide chamber leave gaps deep enough for later rocks
+    // to fall a long way down.
+    let params = Params {
+        rocks: parse_rocks("#/#/# ##").unwrap(),
+        chamber_width: 10,
+        spawn_x: 4,
+        ..Params::default()
+    };
+    let extrapolated = simulate(jets, &params, 8000, true, |_| true);
+    let simulated = simulate(jets, &params, 8000, false, |_| true);
+    assert!(extrapolated.cycle.is_some());
+    assert!(extrapolated.heights.len() < 8000);
+    for rocks in [0, 1, 2022, 5555, 8000] {
+        assert_eq!(
+            extrapolated.get_height(rocks),
+            simulated.get_height(rocks),
+            "{rocks}"
+        );
+    }
+
+    // Rocks pushed left fall all the way to the floor while the rest pile up
+    // on the right, so the tower never repeats.
+    let jets = format!("<<<<{}", ">".repeat(80));
+    let params = Params {
+        rocks: parse_rocks("###").unwrap(),
+        chamber_width: 6,
+        ..Params::default()
+    };
+    let chamber = simulate(&jets, &params, 2000, true, |_| true);
+    assert!(chamber.cycle.is_none());
+    assert!(chamber.get_height(2000).is_ok());
+    assert!(chamber.get_height(params.part2_rocks).is_err());
+}
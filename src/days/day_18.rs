@@ -4,9 +4,35 @@ use std::{
     str::FromStr,
 };
 
-const SEARCH_SPACE: i32 = 22;
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub search_space: i32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params { search_space: 22 }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "search_space" => self.search_space = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
     let cubes: HashSet<Coords> = input
         .lines()
         .map(|line| {
@@ -17,7 +43,12 @@ pub fn main(input: &str) -> (u32, u32) {
         .collect();
 
     let mut exterior = HashSet::new();
-    spread_outside(&cubes, &mut exterior, Coords { x: 0, y: 0, z: 0 });
+    spread_outside(
+        &cubes,
+        &mut exterior,
+        params.search_space,
+        Coords { x: 0, y: 0, z: 0 },
+    );
 
     let (surface_area, external_surface_area) =
         cubes
@@ -38,19 +69,24 @@ pub fn main(input: &str) -> (u32, u32) {
     (surface_area, external_surface_area)
 }
 
-fn spread_outside(cubes: &HashSet<Coords>, exterior: &mut HashSet<Coords>, pos: Coords) {
+fn spread_outside(
+    cubes: &HashSet<Coords>,
+    exterior: &mut HashSet<Coords>,
+    search_space: i32,
+    pos: Coords,
+) {
     for c in pos.adjacent_coords() {
         if !exterior.contains(&c)
             && !cubes.contains(&c)
             && c.x >= -1
-            && c.x <= SEARCH_SPACE
+            && c.x <= search_space
             && c.y >= -1
-            && c.y <= SEARCH_SPACE
+            && c.y <= search_space
             && c.z >= -1
-            && c.z <= SEARCH_SPACE
+            && c.z <= search_space
         {
             exterior.insert(c);
-            spread_outside(cubes, exterior, c);
+            spread_outside(cubes, exterior, search_space, c);
         }
     }
 }
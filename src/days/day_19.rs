@@ -5,7 +5,43 @@ use std::{
     str::FromStr,
 };
 
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub part1_minutes: u32,
+    pub part2_minutes: u32,
+    pub part2_blueprints: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            part1_minutes: 24,
+            part2_minutes: 32,
+            part2_blueprints: 3,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "part1_minutes" => self.part1_minutes = parse_param(key, value)?,
+            "part2_minutes" => self.part2_minutes = parse_param(key, value)?,
+            "part2_blueprints" => self.part2_blueprints = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
+
 pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
     let blueprints: Vec<Blueprint> = Blueprint::transform_example(input)
         .lines()
         .map(|line| Blueprint::from_str(line).unwrap())
@@ -23,14 +59,14 @@ pub fn main(input: &str) -> (u32, u32) {
                 ..Inventory::default()
             },
             Inventory::default(),
-            24,
+            params.part1_minutes,
         );
         let max = max.into_inner();
         p1 += max.geodes * blueprint.id;
     }
 
     let mut p2 = 1;
-    for blueprint in blueprints.iter().take(3) {
+    for blueprint in blueprints.iter().take(params.part2_blueprints) {
         let max = RefCell::new(Inventory::default());
         test_blueprint(
             &max,
@@ -41,7 +77,7 @@ pub fn main(input: &str) -> (u32, u32) {
                 ..Inventory::default()
             },
             Inventory::default(),
-            32,
+            params.part2_minutes,
         );
         let max = max.into_inner();
         p2 *= max.geodes;
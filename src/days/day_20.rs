@@ -1,6 +1,37 @@
-const DECRYPTION_KEY: i64 = 811589153;
+use crate::params::{parse_param, unknown_param, Parameters};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub decryption_key: i64,
+    pub mix_rounds: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            decryption_key: 811589153,
+            mix_rounds: 10,
+        }
+    }
+}
+
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "decryption_key" => self.decryption_key = parse_param(key, value)?,
+            "mix_rounds" => self.mix_rounds = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (i64, i64) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (i64, i64) {
     let mut coords: Vec<i64> = input.lines().map(|line| line.parse().unwrap()).collect();
 
     (
@@ -18,9 +49,9 @@ pub fn main(input: &str) -> (i64, i64) {
         },
         {
             for c in &mut coords {
-                *c = *c * DECRYPTION_KEY;
+                *c = *c * params.decryption_key;
             }
-            let mixed = mix_coords(&coords, 10);
+            let mixed = mix_coords(&coords, params.mix_rounds);
             let index_of_0 = mixed
                 .iter()
                 .enumerate()
@@ -1,6 +1,10 @@
 use std::{fmt::Display, str::FromStr};
 
-use crate::{coord::Coord, direction::Direction};
+use crate::{
+    coord::Coord,
+    direction::Direction,
+    image::{Canvas, Palette, Raster, Recorder, Rgb},
+};
 
 /*
 
@@ -63,6 +67,11 @@ pub fn main(input: &str) -> (u32, u32) {
     (flat_you.get_password(), folded_you.get_password())
 }
 
+pub fn export(input: &str, recorder: &mut Recorder) {
+    let map = Map::from_str(input.split("\n\n").next().unwrap()).unwrap();
+    recorder.frame(&map);
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Position {
     pos: Coord<usize>,
@@ -338,6 +347,16 @@ impl Map {
     }
 }
 
+impl Raster for Map {
+    fn raster(&self) -> Canvas {
+        let width = self.tiles.iter().map(Vec::len).max().unwrap_or(0);
+
+        Canvas::from_fn(width, self.tiles.len(), |x, y| {
+            self.get(Coord(x, y)).color()
+        })
+    }
+}
+
 impl FromStr for Map {
     type Err = String;
 
@@ -404,6 +423,16 @@ impl Display for Tile {
     }
 }
 
+impl Palette for Tile {
+    fn color(&self) -> Rgb {
+        match self {
+            Tile::Empty => Rgb::WHITE,
+            Tile::Wall => Rgb::GREY,
+            Tile::Void => Rgb::BLACK,
+        }
+    }
+}
+
 impl TryFrom<char> for Tile {
     type Error = String;
 
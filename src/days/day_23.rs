@@ -4,15 +4,51 @@ use std::{
     str::FromStr,
 };
 
-use crate::{coord::Coord, direction::Direction, range::Range};
+use crate::{
+    coord::Coord,
+    direction::Direction,
+    image::{Canvas, Framed, Raster, Recorder, Rgb, TileMap},
+    range::Range,
+    visualize::Visualizer,
+};
 
 pub fn main(input: &str) -> (u64, u64) {
+    let (p1, p2) = simulate(input, |_| true);
+
+    (p1.unwrap(), p2.unwrap())
+}
+
+pub fn visualize(input: &str, visualizer: &mut Visualizer) {
+    simulate(input, |map| visualizer.frame(map));
+}
+
+pub fn export(input: &str, recorder: &mut Recorder) {
+    // A first run finds everywhere the elves get to, so that every frame can
+    // be drawn the same size.
+    let mut frames = 0;
+    let mut bounds: Option<(Range, Range)> = None;
+    simulate(input, |map| {
+        frames += 1;
+        let (x, y) = map.bounds();
+        bounds = Some(bounds.map_or((x, y), |(bx, by)| (bx.hull(x), by.hull(y))));
+        true
+    });
+
+    let Some((x, y)) = bounds else {
+        return;
+    };
+    recorder.expect_frames(frames);
+    simulate(input, |map| recorder.frame(&Framed { state: map, x, y }));
+}
+
+fn simulate(input: &str, mut on_round: impl FnMut(&Map) -> bool) -> (Option<u64>, Option<u64>) {
     let mut map = Map::from_str(input).unwrap();
 
     let mut round = 0;
     let mut p1: Option<u64> = None;
     let mut p2: Option<u64> = None;
-    while p1.is_none() || p2.is_none() {
+    let mut watching = on_round(&map);
+    while watching && (p1.is_none() || p2.is_none()) {
         let moved = map.do_round(round);
 
         round += 1;
@@ -24,9 +60,11 @@ pub fn main(input: &str) -> (u64, u64) {
         if round == 10 {
             p1 = Some(map.count_empty_inside());
         }
+
+        watching = on_round(&map);
     }
 
-    (p1.unwrap(), p2.unwrap())
+    (p1, p2)
 }
 
 #[derive(Debug, Clone)]
@@ -129,6 +167,26 @@ impl FromStr for Map {
     }
 }
 
+impl TileMap for Map {
+    fn bounds(&self) -> (Range, Range) {
+        (self.x_range, self.y_range)
+    }
+
+    fn color_at(&self, x: i64, y: i64) -> Rgb {
+        if self.elves.contains(&Coord(x, y)) {
+            Rgb::GREEN
+        } else {
+            Rgb::BLACK
+        }
+    }
+}
+
+impl Raster for Map {
+    fn raster(&self) -> Canvas {
+        self.raster_within(self.x_range, self.y_range)
+    }
+}
+
 impl Display for Map {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for y in self.y_range {
@@ -4,30 +4,131 @@ use std::{
     str::FromStr,
 };
 
-use crate::{coord::Coord, direction::Direction};
+use crate::{
+    coord::Coord,
+    direction::Direction,
+    params::{parse_param, unknown_param, Parameters},
+    visualize::Visualizer,
+};
+
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub search_limit: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params { search_limit: 500 }
+    }
+}
 
-const SEARCH_LIMIT: u32 = 500;
+impl Parameters for Params {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "search_limit" => self.search_limit = parse_param(key, value)?,
+            key => return unknown_param(key),
+        }
+
+        Ok(())
+    }
+}
 
 pub fn main(input: &str) -> (u32, u32) {
+    main_with_params(input, &Params::default())
+}
+
+pub fn main_with_params(input: &str, params: &Params) -> (u32, u32) {
     let valley = Valley::from_str(input).unwrap();
+    let limit = params.search_limit;
 
-    let (first, valley) = find_min((valley.start, Direction::Down), valley.end, valley).unwrap();
-    let (second, valley) = find_min((valley.end, Direction::Up), valley.start, valley).unwrap();
-    let (third, _) = find_min((valley.start, Direction::Down), valley.end, valley).unwrap();
+    let (first, valley) =
+        find_min((valley.start, Direction::Down), valley.end, valley, limit).unwrap();
+    let (second, valley) =
+        find_min((valley.end, Direction::Up), valley.start, valley, limit).unwrap();
+    let (third, _) = find_min((valley.start, Direction::Down), valley.end, valley, limit).unwrap();
 
     (first, first + second + third)
 }
 
+pub fn visualize(input: &str, params: &Params, visualizer: &mut Visualizer) {
+    let valley = Valley::from_str(input).unwrap();
+    let (start, end) = (valley.start, valley.end);
+
+    let mut frames = vec![valley];
+    for dest in [end, start, end] {
+        let valley = frames.pop().unwrap();
+        frames.extend(find_path(valley, dest, params.search_limit).unwrap());
+    }
+
+    for frame in &frames {
+        if !visualizer.frame(frame) {
+            break;
+        }
+    }
+}
+
+/// Breadth-first search over every position reachable at each minute, keeping
+/// each minute's valley so the route can be replayed one frame at a time.
+fn find_path(mut valley: Valley, dest: Coord<usize>, search_limit: u32) -> Option<Vec<Valley>> {
+    let mut reachable: HashMap<Coord<usize>, Coord<usize>> = HashMap::new();
+    reachable.insert(valley.expedition, valley.expedition);
+    let mut minutes = vec![(valley.clone(), reachable.clone())];
+
+    for _ in 0..search_limit {
+        valley.advance_blizzards();
+
+        let mut next: HashMap<Coord<usize>, Coord<usize>> = HashMap::new();
+        for pos in reachable.keys().copied() {
+            for c in [
+                Some(pos),
+                pos.1.checked_sub(1).map(|y| Coord(pos.0, y)),
+                Some(Coord(pos.0, pos.1 + 1)),
+                pos.0.checked_sub(1).map(|x| Coord(x, pos.1)),
+                Some(Coord(pos.0 + 1, pos.1)),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if valley.is_open(c) {
+                    next.entry(c).or_insert(pos);
+                }
+            }
+        }
+
+        let found = next.contains_key(&dest);
+        minutes.push((valley.clone(), next.clone()));
+        reachable = next;
+
+        if found {
+            let mut frames = Vec::with_capacity(minutes.len());
+            let mut pos = dest;
+            for (valley, parents) in minutes.into_iter().rev() {
+                frames.push(Valley {
+                    expedition: pos,
+                    ..valley
+                });
+                pos = parents[&pos];
+            }
+            frames.reverse();
+
+            return Some(frames);
+        }
+    }
+
+    None
+}
+
 fn find_min(
     (start, start_dir): (Coord<usize>, Direction),
     dest: Coord<usize>,
     valley: Valley,
+    search_limit: u32,
 ) -> Option<(u32, Valley)> {
     let mut cache: HashMap<String, u32> = HashMap::new();
     let mut stack: VecDeque<(Valley, u32)> = VecDeque::new();
     stack.push_front((valley, 0));
 
-    let mut min = SEARCH_LIMIT;
+    let mut min = search_limit;
     let mut min_valley_end: Option<Valley> = None;
 
     while !stack.is_empty() {
@@ -121,6 +222,16 @@ struct Valley {
 }
 
 impl Valley {
+    fn is_open(&self, pos: Coord<usize>) -> bool {
+        pos == self.start
+            || pos == self.end
+            || (pos.0 > 0
+                && pos.0 <= self.bound.0
+                && pos.1 > 0
+                && pos.1 <= self.bound.1
+                && !self.blizzards.contains_key(&pos))
+    }
+
     fn advance_blizzards(&mut self) {
         let mut blizzards: Blizzards = HashMap::new();
         for (pos, dirs) in &self.blizzards {
@@ -237,3 +348,28 @@ impl FromStr for Valley {
         })
     }
 }
+
+#[test]
+fn find_path_works() {
+    let valley = Valley::from_str(
+        "
+#.######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#"
+            .trim(),
+    )
+    .unwrap();
+    let (start, end) = (valley.start, valley.end);
+
+    let there = find_path(valley, end, 500).unwrap();
+    assert_eq!(there.len() - 1, 18);
+
+    let back = find_path(there.last().unwrap().clone(), start, 500).unwrap();
+    assert_eq!(back.len() - 1, 23);
+
+    let there_again = find_path(back.last().unwrap().clone(), end, 500).unwrap();
+    assert_eq!(there_again.len() - 1, 13);
+}
@@ -0,0 +1,391 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::range::Range;
+
+/// How many frames an animation gets when `every` isn't chosen and the
+/// simulation says how many it will make.
+const DEFAULT_FRAMES: usize = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0x0f, 0x0f, 0x23);
+    pub const WHITE: Rgb = Rgb(0xcc, 0xcc, 0xcc);
+    pub const GOLD: Rgb = Rgb(0xff, 0xcc, 0x33);
+    pub const GREEN: Rgb = Rgb(0x00, 0x99, 0x00);
+    pub const GREY: Rgb = Rgb(0x66, 0x66, 0x66);
+}
+
+/// Maps a tile to the colour it's drawn with.
+pub trait Palette {
+    fn color(&self) -> Rgb;
+}
+
+/// A state that can be drawn as a grid of pixels, one per tile.
+pub trait Raster {
+    fn raster(&self) -> Canvas;
+}
+
+/// A state laid out on an unbounded grid, which can be drawn within any
+/// bounds.
+pub trait TileMap {
+    /// The columns and rows with anything worth drawing in them.
+    fn bounds(&self) -> (Range, Range);
+
+    fn color_at(&self, x: i64, y: i64) -> Rgb;
+
+    fn raster_within(&self, x: Range, y: Range) -> Canvas {
+        Canvas::from_fn(x.width() as usize, y.width() as usize, |dx, dy| {
+            self.color_at(x.0 + dx as i64, y.0 + dy as i64)
+        })
+    }
+}
+
+/// Draws a [`TileMap`] within fixed bounds rather than its own, so that every
+/// frame of an animation lines up even as the state grows.
+pub struct Framed<'a, T: TileMap> {
+    pub state: &'a T,
+    pub x: Range,
+    pub y: Range,
+}
+
+impl<T: TileMap> Raster for Framed<'_, T> {
+    fn raster(&self) -> Canvas {
+        self.state.raster_within(self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| f(x, y))
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Blows each pixel up into a `factor` x `factor` square.
+    pub fn scaled(&self, factor: usize) -> Canvas {
+        Canvas::from_fn(self.width * factor, self.height * factor, |x, y| {
+            self.get(x / factor, y / factor)
+        })
+    }
+
+    fn rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|Rgb(r, g, b)| [*r, *g, *b])
+            .collect()
+    }
+
+    pub fn write_ppm(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.rgb_bytes())
+    }
+
+    #[cfg(feature = "images")]
+    pub fn write_png(&self, w: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.rgb_bytes()))
+            .map_err(io::Error::other)
+    }
+
+    /// The canvas's size as a GIF stores it, which is at most 65535 pixels
+    /// each way.
+    #[cfg(feature = "images")]
+    fn gif_size(&self) -> io::Result<(u16, u16)> {
+        match (u16::try_from(self.width), u16::try_from(self.height)) {
+            (Ok(width), Ok(height)) => Ok((width, height)),
+            _ => Err(io::Error::other(format!(
+                "{}x{} is too big for a GIF, which can be at most {}x{}",
+                self.width,
+                self.height,
+                u16::MAX,
+                u16::MAX
+            ))),
+        }
+    }
+
+    #[cfg(feature = "images")]
+    fn gif_frame(&self, delay: u16) -> io::Result<gif::Frame<'static>> {
+        let (width, height) = self.gif_size()?;
+        let mut palette: Vec<Rgb> = Vec::new();
+        let mut indices = Vec::with_capacity(self.pixels.len());
+
+        for pixel in &self.pixels {
+            match palette.iter().position(|c| c == pixel) {
+                Some(i) => indices.push(i as u8),
+                None if palette.len() < 256 => {
+                    indices.push(palette.len() as u8);
+                    palette.push(*pixel);
+                }
+                None => {
+                    let mut frame =
+                        gif::Frame::from_rgb_speed(width, height, &self.rgb_bytes(), 10);
+                    frame.delay = delay;
+                    return Ok(frame);
+                }
+            }
+        }
+
+        let palette: Vec<u8> = palette
+            .into_iter()
+            .flat_map(|Rgb(r, g, b)| [r, g, b])
+            .collect();
+        let mut frame = gif::Frame::from_palette_pixels(width, height, indices, palette, None);
+        frame.delay = delay;
+        Ok(frame)
+    }
+}
+
+impl Raster for Canvas {
+    fn raster(&self) -> Canvas {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ppm,
+    Png,
+    Gif,
+}
+
+/// Writes simulation frames out as images as they arrive.
+///
+/// `.ppm` and `.png` paths get one numbered file per frame, while a `.gif`
+/// path gets a single looping animation of every frame, all of which must be
+/// the same size as the first.
+pub struct Recorder {
+    path: PathBuf,
+    format: Format,
+    scale: usize,
+    /// Records every nth frame, if chosen up front.
+    every: Option<usize>,
+    default_every: usize,
+    frame_delay: u16,
+    seen: usize,
+    written: usize,
+    size: Option<(usize, usize)>,
+    #[cfg(feature = "images")]
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl Recorder {
+    pub fn new(path: impl AsRef<Path>, scale: usize, every: Option<usize>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => Format::Ppm,
+            Some("png") => Format::Png,
+            Some("gif") => Format::Gif,
+            _ => return Err(format!("unsupported image format: {}", path.display())),
+        };
+
+        if format != Format::Ppm && cfg!(not(feature = "images")) {
+            return Err(format!("{} requires the `images` feature", path.display()));
+        }
+
+        Ok(Recorder {
+            path,
+            format,
+            scale: scale.max(1),
+            every: every.map(|every| every.max(1)),
+            default_every: 1,
+            frame_delay: 5,
+            seen: 0,
+            written: 0,
+            size: None,
+            #[cfg(feature = "images")]
+            gif: None,
+        })
+    }
+
+    /// Sets the delay between animation frames, in hundredths of a second.
+    pub fn set_frame_delay(&mut self, delay: u16) {
+        self.frame_delay = delay;
+    }
+
+    /// Lets the recorder know how many frames are coming, so that unless
+    /// `every` was chosen, long runs are thinned out to about
+    /// [`DEFAULT_FRAMES`] frames.
+    pub fn expect_frames(&mut self, frames: usize) {
+        self.default_every = frames.div_ceil(DEFAULT_FRAMES).max(1);
+    }
+
+    /// Records `state`, skipping frames according to `every`.
+    ///
+    /// Returns `false` if the frame could not be written, so the simulation
+    /// can stop early.
+    pub fn frame(&mut self, state: &impl Raster) -> bool {
+        let index = self.seen;
+        self.seen += 1;
+        if !index.is_multiple_of(self.every.unwrap_or(self.default_every)) {
+            return true;
+        }
+
+        let canvas = state.raster().scaled(self.scale);
+        match self.write_frame(&canvas) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("failed to write frame {}: {e}", self.written);
+                false
+            }
+        }
+    }
+
+    fn frame_path(&self) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("frame");
+        let ext = self
+            .path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+
+        self.path
+            .with_file_name(format!("{stem}-{:05}.{ext}", self.written))
+    }
+
+    fn write_frame(&mut self, canvas: &Canvas) -> io::Result<()> {
+        let size = (canvas.width, canvas.height);
+        match self.size {
+            Some(expected) if self.format == Format::Gif && expected != size => {
+                return Err(io::Error::other(format!(
+                    "frame is {}x{}, but the animation is {}x{}",
+                    size.0, size.1, expected.0, expected.1
+                )));
+            }
+            Some(_) => {}
+            None => self.size = Some(size),
+        }
+
+        match self.format {
+            Format::Ppm => canvas.write_ppm(BufWriter::new(File::create(self.frame_path())?))?,
+            #[cfg(feature = "images")]
+            Format::Png => canvas.write_png(BufWriter::new(File::create(self.frame_path())?))?,
+            #[cfg(feature = "images")]
+            Format::Gif => {
+                let frame = canvas.gif_frame(self.frame_delay)?;
+                let encoder = match &mut self.gif {
+                    Some(encoder) => encoder,
+                    None => {
+                        let (width, height) = canvas.gif_size()?;
+                        let file = BufWriter::new(File::create(&self.path)?);
+                        let mut encoder = gif::Encoder::new(file, width, height, &[])
+                            .map_err(io::Error::other)?;
+                        encoder
+                            .set_repeat(gif::Repeat::Infinite)
+                            .map_err(io::Error::other)?;
+                        self.gif.insert(encoder)
+                    }
+                };
+                encoder.write_frame(&frame).map_err(io::Error::other)?;
+            }
+            #[cfg(not(feature = "images"))]
+            _ => unreachable!("{:?} frames need the `images` feature", self.format),
+        }
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Finishes off the animation, if there is one, returning how many frames
+    /// were saved.
+    pub fn finish(self) -> io::Result<usize> {
+        #[cfg(feature = "images")]
+        if let Some(encoder) = self.gif {
+            encoder.into_inner()?.flush()?;
+        }
+
+        Ok(self.written)
+    }
+}
+
+#[test]
+fn ppm_works() {
+    let mut canvas = Canvas::new(2, 1, Rgb::BLACK);
+    canvas.set(1, 0, Rgb(1, 2, 3));
+
+    let mut ppm = Vec::new();
+    canvas.scaled(2).write_ppm(&mut ppm).unwrap();
+
+    let header = b"P6\n4 2\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(
+        &ppm[header.len()..],
+        [Rgb::BLACK, Rgb::BLACK, Rgb(1, 2, 3), Rgb(1, 2, 3)]
+            .repeat(2)
+            .iter()
+            .flat_map(|Rgb(r, g, b)| [*r, *g, *b])
+            .collect::<Vec<u8>>()
+    );
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn gif_works() {
+    let path = std::env::temp_dir().join(format!("gif-works-{}.gif", std::process::id()));
+
+    let mut recorder = Recorder::new(&path, 2, None).unwrap();
+    recorder.expect_frames(DEFAULT_FRAMES * 2);
+    for _ in 0..4 {
+        assert!(recorder.frame(&Canvas::new(3, 2, Rgb::BLACK)));
+    }
+    // Frames that don't match the first would make the animation jitter.
+    assert!(!recorder.frame(&Canvas::new(4, 2, Rgb::BLACK)));
+    assert_eq!(recorder.finish().unwrap(), 2);
+
+    let gif = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(&gif[6..10], &[6, 0, 4, 0]);
+
+    assert!(Canvas::new(70000, 1, Rgb::BLACK).gif_size().is_err());
+}
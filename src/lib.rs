@@ -3,8 +3,11 @@ use std::io::{self, Read};
 mod coord;
 pub mod days;
 mod direction;
+pub mod image;
 mod operation;
+pub mod params;
 mod range;
+pub mod visualize;
 
 pub fn get_input() -> io::Result<String> {
     let mut s = String::new();
@@ -1,18 +1,87 @@
-use adventofcode_2022::days;
+use adventofcode_2022::{
+    days,
+    image::Recorder,
+    params::{parse_override, Parameters},
+    visualize::Visualizer,
+};
 use std::{
     env,
-    io::{self, Read, Result},
+    fs::File,
+    io::{self, BufReader, Read, Result},
 };
 
+const DAYS_WITH_PARAMS: &[u32] = &[7, 10, 11, 12, 14, 15, 16, 17, 18, 19, 20, 24];
+const DEFAULT_FPS: f64 = 10.0;
+const DEFAULT_SCALE: usize = 4;
+
 fn main() {
-    let day: u32 = env::args()
-        .skip(1)
-        .next()
-        .expect("missing day argument")
-        .parse()
-        .unwrap();
+    let args = Args::parse();
+    let day = args.day;
     let input = get_input().unwrap();
 
+    if !args.params.is_empty() && !DAYS_WITH_PARAMS.contains(&day) {
+        panic!("day {day} has no parameters");
+    }
+
+    if args.debugger {
+        if day != 10 {
+            panic!("day {day} has no debugger");
+        }
+
+        let tty = File::open("/dev/tty").expect("debugger needs a terminal for commands");
+        days::day_10::debug(
+            input.trim(),
+            &args.params(),
+            BufReader::new(tty),
+            io::stdout(),
+        )
+        .unwrap();
+        return;
+    }
+
+    if args.explain {
+        match day {
+            13 => print!("{}", days::day_13::explain(input.trim())),
+            16 => print!("{}", days::day_16::explain(input.trim(), &args.params())),
+            _ => panic!("day {day} has no explanations"),
+        }
+        return;
+    }
+
+    if args.visualize {
+        let mut visualizer = Visualizer::new(args.fps, args.paused);
+        match day {
+            9 => days::day_09::visualize(input.trim(), &mut visualizer),
+            14 => days::day_14::visualize(input.trim(), &args.params(), &mut visualizer),
+            17 => days::day_17::visualize(input.trim(), &args.params(), &mut visualizer),
+            23 => days::day_23::visualize(input.trim(), &mut visualizer),
+            24 => days::day_24::visualize(input.trim(), &args.params(), &mut visualizer),
+            _ => panic!("day {day} has no visualization"),
+        }
+        return;
+    }
+
+    if let Some(path) = &args.export {
+        if day == 16 {
+            let dot = days::day_16::dot(input.trim(), &args.params());
+            std::fs::write(path, dot).unwrap();
+            eprintln!("Wrote valve graph to {path}");
+            return;
+        }
+
+        let mut recorder = Recorder::new(path, args.scale, args.every).unwrap();
+        match day {
+            14 => days::day_14::export(input.trim(), &args.params(), &mut recorder),
+            17 => days::day_17::export(input.trim(), &args.params(), &mut recorder),
+            22 => days::day_22::export(&input, &mut recorder),
+            23 => days::day_23::export(input.trim(), &mut recorder),
+            _ => panic!("day {day} has no image export"),
+        }
+        let frames = recorder.finish().unwrap();
+        eprintln!("Wrote {frames} frames to {path}");
+        return;
+    }
+
     let results = match day {
         1 => stringify_u32(days::day_01::main(input.trim())),
         2 => stringify_u32(days::day_02::main(input.trim())),
@@ -20,24 +89,24 @@ fn main() {
         4 => stringify_u32(days::day_04::main(input.trim())),
         5 => days::day_05::main(input.trim()),
         6 => stringify_u32(days::day_06::main(input.trim())),
-        7 => stringify_u32(days::day_07::main(input.trim())),
+        7 => stringify_u32(days::day_07::main_with_params(input.trim(), &args.params())),
         8 => stringify_u32(days::day_08::main(input.trim())),
         9 => stringify_u32(days::day_09::main(input.trim())),
-        10 => days::day_10::main(input.trim()),
-        11 => stringify_u64(days::day_11::main(input.trim())),
-        12 => stringify_u32(days::day_12::main(input.trim())),
+        10 => days::day_10::main_with_params(input.trim(), &args.params()),
+        11 => stringify_u64(days::day_11::main_with_params(input.trim(), &args.params())),
+        12 => stringify_u32(days::day_12::main_with_params(input.trim(), &args.params())),
         13 => stringify_u32(days::day_13::main(input.trim())),
-        14 => stringify_u32(days::day_14::main(input.trim())),
-        15 => stringify_u64(days::day_15::main(input.trim())),
-        16 => stringify_u32(days::day_16::main(input.trim())),
-        17 => stringify_u64(days::day_17::main(input.trim())),
-        18 => stringify_u32(days::day_18::main(input.trim())),
-        19 => stringify_u32(days::day_19::main(input.trim())),
-        20 => stringify_i64(days::day_20::main(input.trim())),
+        14 => stringify_u32(days::day_14::main_with_params(input.trim(), &args.params())),
+        15 => stringify_u64(days::day_15::main_with_params(input.trim(), &args.params())),
+        16 => stringify_u32(days::day_16::main_with_params(input.trim(), &args.params())),
+        17 => stringify_u64(days::day_17::main_with_params(input.trim(), &args.params())),
+        18 => stringify_u32(days::day_18::main_with_params(input.trim(), &args.params())),
+        19 => stringify_u32(days::day_19::main_with_params(input.trim(), &args.params())),
+        20 => stringify_i64(days::day_20::main_with_params(input.trim(), &args.params())),
         21 => stringify_u64(days::day_21::main(input.trim())),
         22 => stringify_u32(days::day_22::main(&input)),
         23 => stringify_u64(days::day_23::main(input.trim())),
-        24 => stringify_u32(days::day_24::main(input.trim())),
+        24 => stringify_u32(days::day_24::main_with_params(input.trim(), &args.params())),
         25 => days::day_25::main(input.trim()),
         _ => panic!("unsupported day {day}"),
     };
@@ -46,6 +115,92 @@ fn main() {
     println!("Part 2: {}", results.1);
 }
 
+struct Args {
+    day: u32,
+    params: Vec<(String, String)>,
+    debugger: bool,
+    explain: bool,
+    visualize: bool,
+    fps: f64,
+    paused: bool,
+    export: Option<String>,
+    scale: usize,
+    every: Option<usize>,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = env::args().skip(1);
+        let day: u32 = args.next().expect("missing day argument").parse().unwrap();
+        let mut params = Vec::new();
+        let mut debugger = false;
+        let mut explain = false;
+        let mut visualize = false;
+        let mut fps = DEFAULT_FPS;
+        let mut paused = false;
+        let mut export = None;
+        let mut scale = DEFAULT_SCALE;
+        let mut every = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--param" => {
+                    let param = args.next().expect("missing value for --param");
+                    params.push(parse_override(&param).unwrap());
+                }
+                "--debugger" => debugger = true,
+                "--explain" => explain = true,
+                "--visualize" => visualize = true,
+                "--fps" => {
+                    fps = args
+                        .next()
+                        .expect("missing value for --fps")
+                        .parse()
+                        .expect("invalid value for --fps");
+                    if !fps.is_finite() || fps <= 0.0 {
+                        panic!("--fps must be a positive number, got {fps}");
+                    }
+                }
+                "--paused" => paused = true,
+                "--export" => export = Some(args.next().expect("missing value for --export")),
+                "--scale" => {
+                    scale = args
+                        .next()
+                        .expect("missing value for --scale")
+                        .parse()
+                        .expect("invalid value for --scale");
+                }
+                "--every" => {
+                    every = Some(
+                        args.next()
+                            .expect("missing value for --every")
+                            .parse()
+                            .expect("invalid value for --every"),
+                    );
+                }
+                arg => panic!("unrecognized argument {arg}"),
+            }
+        }
+
+        Args {
+            day,
+            params,
+            debugger,
+            explain,
+            visualize,
+            fps,
+            paused,
+            export,
+            scale,
+            every,
+        }
+    }
+
+    fn params<P: Parameters>(&self) -> P {
+        P::with_overrides(&self.params).unwrap()
+    }
+}
+
 fn stringify_u32(int_results: (u32, u32)) -> (String, String) {
     (int_results.0.to_string(), int_results.1.to_string())
 }
@@ -0,0 +1,91 @@
+use std::{fmt::Debug, str::FromStr};
+
+/// Puzzle parameters for a single day, overridable from the CLI with
+/// `--param key=value`.
+pub trait Parameters: Default {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String>;
+
+    /// Checks the parameters once every override has been applied, for
+    /// constraints that involve more than one of them.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn with_overrides(overrides: &[(String, String)]) -> Result<Self, String> {
+        let mut params = Self::default();
+        for (key, value) in overrides {
+            params.set(key, value)?;
+        }
+        params.validate()?;
+
+        Ok(params)
+    }
+}
+
+pub fn parse_param<T>(key: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid value for parameter {key}: {value} ({:?})", e))
+}
+
+pub fn parse_override(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or(format!("parameter must be in key=value form: {s}"))?;
+
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+pub fn unknown_param(key: &str) -> Result<(), String> {
+    Err(format!("unknown parameter {key}"))
+}
+
+#[test]
+fn overrides_work() {
+    #[derive(Debug, Default, PartialEq)]
+    struct Example {
+        rows: u32,
+        name: String,
+    }
+
+    impl Parameters for Example {
+        fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+            match key {
+                "rows" => self.rows = parse_param(key, value)?,
+                "name" => self.name = value.to_string(),
+                key => return unknown_param(key),
+            }
+
+            Ok(())
+        }
+
+        fn validate(&self) -> Result<(), String> {
+            if self.rows == 0 {
+                return Err("rows must be positive".to_string());
+            }
+
+            Ok(())
+        }
+    }
+
+    let overrides = vec![
+        parse_override("rows=10").unwrap(),
+        parse_override("name = AA").unwrap(),
+    ];
+    assert_eq!(
+        Example::with_overrides(&overrides).unwrap(),
+        Example {
+            rows: 10,
+            name: "AA".to_string()
+        }
+    );
+
+    assert!(Example::with_overrides(&[parse_override("rows=x").unwrap()]).is_err());
+    assert!(Example::with_overrides(&[parse_override("rows=0").unwrap()]).is_err());
+    assert!(Example::with_overrides(&[parse_override("cols=1").unwrap()]).is_err());
+    assert!(parse_override("rows").is_err());
+}
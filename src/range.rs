@@ -76,6 +76,11 @@ impl Range {
         }
     }
 
+    /// The smallest range covering both.
+    pub fn hull(self, other: Range) -> Range {
+        Range(self.0.min(other.0), self.1.max(other.1))
+    }
+
     pub fn add_point(&mut self, point: i64) {
         self.0 = self.0.min(point);
         self.1 = self.1.max(point);
@@ -116,10 +121,6 @@ impl Coverage {
         c
     }
 
-    pub fn ranges(&self) -> &Vec<Range> {
-        &self.ranges
-    }
-
     #[allow(unused)]
     pub fn contains(&self, other: Range) -> bool {
         self.ranges
@@ -127,22 +128,15 @@ impl Coverage {
             .any(|range| range.intersection(other).is_some())
     }
 
-    pub fn bounds(&self) -> Option<Range> {
-        if self.ranges.is_empty() {
-            None
-        } else {
-            Some(Range::new(self.ranges[0].0, self.ranges.last().unwrap().1).unwrap())
-        }
-    }
-
-    pub fn gaps(&self) -> Coverage {
-        let mut ranges = Vec::new();
-
-        for pair in self.ranges.windows(2) {
-            ranges.push(Range::new(pair[0].1 + 1, pair[1].0 - 1).unwrap());
+    /// The parts of the coverage that fall within `range`.
+    pub fn clipped(&self, range: Range) -> Coverage {
+        Coverage {
+            ranges: self
+                .ranges
+                .iter()
+                .filter_map(|r| r.intersection(range))
+                .collect(),
         }
-
-        Coverage { ranges }
     }
 
     pub fn area_covered(&self) -> i64 {
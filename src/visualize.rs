@@ -0,0 +1,186 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const MIN_FPS: f64 = 0.1;
+const MAX_FPS: f64 = 240.0;
+
+/// Renders simulation states to the terminal one frame at a time.
+///
+/// Controls are read a line at a time from `/dev/tty`, since stdin is already
+/// used for the puzzle input:
+///
+/// - `<enter>` pauses, or steps forward a single frame while paused
+/// - `p` toggles between paused and playing
+/// - `+` / `-` doubles / halves the frame rate
+/// - `q` stops the simulation
+pub struct Visualizer {
+    fps: f64,
+    paused: bool,
+    frame: usize,
+    commands: Option<Receiver<Command>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Step,
+    TogglePause,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl Command {
+    fn from_line(line: &str) -> Option<Command> {
+        match line.trim() {
+            "" => Some(Command::Step),
+            "p" => Some(Command::TogglePause),
+            "+" => Some(Command::Faster),
+            "-" => Some(Command::Slower),
+            "q" => Some(Command::Quit),
+            _ => None,
+        }
+    }
+}
+
+impl Visualizer {
+    pub fn new(fps: f64, paused: bool) -> Self {
+        let mut visualizer = Visualizer {
+            fps: MAX_FPS,
+            paused,
+            frame: 0,
+            commands: Visualizer::spawn_controls().ok(),
+        };
+        visualizer.set_fps(fps);
+
+        visualizer
+    }
+
+    /// Sets the frame rate, keeping it between [`MIN_FPS`] and [`MAX_FPS`].
+    fn set_fps(&mut self, fps: f64) {
+        self.fps = if fps.is_nan() {
+            MIN_FPS
+        } else {
+            fps.clamp(MIN_FPS, MAX_FPS)
+        };
+    }
+
+    fn spawn_controls() -> io::Result<Receiver<Command>> {
+        let tty = BufReader::new(File::open("/dev/tty")?);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in tty.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                if let Some(command) = Command::from_line(&line) {
+                    if sender.send(command).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(receiver)
+    }
+
+    /// Draws `state` and waits until the next frame is due.
+    ///
+    /// Returns `false` once the viewer has asked to stop.
+    pub fn frame(&mut self, state: &impl Display) -> bool {
+        self.frame += 1;
+
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "{CLEAR_SCREEN}{state}\n{}\n", self.status());
+        let _ = stdout.flush();
+        drop(stdout);
+
+        loop {
+            let command = match (&self.commands, self.paused) {
+                (Some(commands), true) => match commands.recv() {
+                    Ok(command) => command,
+                    Err(_) => {
+                        self.paused = false;
+                        continue;
+                    }
+                },
+                (Some(commands), false) => match commands.recv_timeout(self.frame_delay()) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => return true,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        self.commands = None;
+                        continue;
+                    }
+                },
+                (None, _) => {
+                    thread::sleep(self.frame_delay());
+                    return true;
+                }
+            };
+
+            match command {
+                Command::Step => {
+                    if self.paused {
+                        return true;
+                    }
+                    self.paused = true;
+                }
+                Command::TogglePause => {
+                    self.paused = !self.paused;
+                    if !self.paused {
+                        return true;
+                    }
+                }
+                Command::Faster => self.set_fps(self.fps * 2.0),
+                Command::Slower => self.set_fps(self.fps / 2.0),
+                Command::Quit => return false,
+            }
+        }
+    }
+
+    fn frame_delay(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps)
+    }
+
+    fn status(&self) -> String {
+        format!(
+            "frame {} | {:.1} fps{} | <enter>: step, p: play/pause, +/-: speed, q: quit",
+            self.frame,
+            self.fps,
+            if self.paused { " | paused" } else { "" }
+        )
+    }
+}
+
+#[test]
+fn commands_parse() {
+    assert_eq!(Command::from_line("\n"), Some(Command::Step));
+    assert_eq!(Command::from_line(" p "), Some(Command::TogglePause));
+    assert_eq!(Command::from_line("+"), Some(Command::Faster));
+    assert_eq!(Command::from_line("-"), Some(Command::Slower));
+    assert_eq!(Command::from_line("q"), Some(Command::Quit));
+    assert_eq!(Command::from_line("x"), None);
+}
+
+#[test]
+fn fps_is_clamped() {
+    for fps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let visualizer = Visualizer::new(fps, false);
+        assert!((MIN_FPS..=MAX_FPS).contains(&visualizer.fps), "{fps}");
+        visualizer.frame_delay();
+    }
+
+    let mut visualizer = Visualizer::new(1.0, false);
+    for _ in 0..100 {
+        visualizer.set_fps(visualizer.fps / 2.0);
+    }
+    assert_eq!(visualizer.fps, MIN_FPS);
+}